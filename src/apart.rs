@@ -0,0 +1,119 @@
+use std::rc::Rc;
+
+use crate::factor::{factor_list, fraction, leading_in};
+use crate::node::*;
+use crate::poly::{negate_leading, number, var_name, Atom, Poly};
+use crate::rational::Rational;
+
+use BKind::*;
+
+// Division of polynomials in `x` by a `b` with constant coefficients, so that
+// the quotient needs no fractions of the other atoms.
+fn divrem(a: &Poly, b: &Poly, x: &Atom) -> Result<(Poly, Poly), String> {
+    let db = b.degree_in(x);
+    let lb = leading_in(b, x).as_constant().expect("the divisor has constant coefficients");
+    let mut quot = Poly::constant(Rational::int(0));
+    let mut rem = a.clone();
+    while !rem.is_zero() && rem.degree_in(x) >= db {
+        let term = leading_in(&rem, x).mul(&b.power_of(x, rem.degree_in(x) - db))?.mul(&Poly::constant(lb.recip()?))?;
+        quot = quot.add(&term)?;
+        rem = rem.sub(&term.mul(b)?)?;
+    }
+    Ok((quot, rem))
+}
+
+// The inverse of `a` modulo a coprime `m`, by the extended Euclidean algorithm.
+fn inverse(a: &Poly, m: &Poly, x: &Atom) -> Result<Poly, String> {
+    let (mut r0, mut r1) = (m.clone(), divrem(a, m, x)?.1);
+    let (mut t0, mut t1) = (Poly::constant(Rational::int(0)), Poly::one());
+    while !r1.is_zero() {
+        let (q, r) = divrem(&r0, &r1, x)?;
+        (r0, r1) = (r1, r);
+        let t = t0.sub(&q.mul(&t1)?)?;
+        (t0, t1) = (t1, t);
+    }
+    t0.mul(&Poly::constant(r0.as_constant().expect("coprime polynomials have a constant gcd").recip()?))
+}
+
+// `r / f ^ k`, with the constant factor of `r` pulled out. The flag is set when
+// that factor is negative and the term has been written as its negation.
+fn term(r: &Poly, f: &Poly, k: u32) -> Result<(Rc<Node>, bool), String> {
+    let c = r.content()?;
+    let r = r.primitive()?;
+    let num = match (c.num.abs(), r.as_constant()) {
+        (n, Some(_)) => number(Rational::int(n))?,
+        (1, None) => r.to_node()?,
+        (n, None) => Node::binary(Mul, number(Rational::int(n))?, r.to_node()?),
+    };
+    let power = if k == 1 { f.to_node()? } else { Node::binary(Pow, f.to_node()?, Node::num(k as f32)) };
+    let den = if c.den == 1 { power } else { Node::binary(Mul, number(Rational::int(c.den))?, power) };
+    Ok((Node::binary(Div, num, den), c.num < 0))
+}
+
+// Decomposes a rational function of `var` into a polynomial plus a sum of
+// terms `r / f ^ k`, one for each power of each irreducible factor `f` of the
+// denominator, with `r` of lower degree than `f`. The denominator must have
+// rational coefficients; the numerator may contain other variables.
+pub fn apart(node: &Rc<Node>, var: &Rc<Node>) -> Result<Rc<Node>, String> {
+    let name = var_name(var)?;
+    let x = Atom::Var(name.clone());
+    let (p, q) = fraction(node)?;
+    p.check_polynomial(&name)?;
+    q.check_polynomial(&name)?;
+    if q.atoms().iter().any(|atom| *atom != x) {
+        return Err(format!("the denominator {} does not have rational coefficients", q.to_node()?));
+    }
+    let (quot, rem) = divrem(&p, &q, &x)?;
+
+    let mut terms = Vec::new();
+    if !quot.is_zero() {
+        terms.push((quot.to_node()?, false));
+    }
+    let (c, factors) = factor_list(&q, true)?;
+    let rem = rem.mul(&Poly::constant(c.recip()?))?;
+    for (i, (f, m)) in factors.iter().enumerate() {
+        let power = f.pow(*m)?;
+        let rest = factors.iter().enumerate().filter(|(j, _)| *j != i).try_fold(Poly::one(), |acc, (_, (g, n))| acc.mul(&g.pow(*n)?))?;
+        let mut a = divrem(&rem.mul(&inverse(&rest, &power, &x)?)?, &power, &x)?.1;
+        // Writing `a` in base `f` as `r0 + r1 f + ...` gives
+        // `a / f ^ m = r0 / f ^ m + r1 / f ^ (m - 1) + ...`.
+        let mut numerators = Vec::new();
+        for _ in 0..*m {
+            let (quot, digit) = divrem(&a, f, &x)?;
+            numerators.push(digit);
+            a = quot;
+        }
+        for (k, r) in numerators.iter().enumerate().rev() {
+            if !r.is_zero() {
+                terms.push(term(r, f, m - k as u32)?);
+            }
+        }
+    }
+
+    let mut res: Option<Rc<Node>> = None;
+    for (term, negative) in terms {
+        res = Some(match (res, negative) {
+            (None, false) => term,
+            (None, true) => negate_leading(&term),
+            (Some(sum), false) => Node::binary(Add, sum, term),
+            (Some(sum), true) => Node::binary(Sub, sum, term),
+        });
+    }
+    Ok(res.unwrap_or_else(|| Node::num(0.0)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    fn partial(src: &str) -> String {
+        apart(&parse(&format!("var x; {}", src)), &Node::var("x", None)).unwrap().to_string()
+    }
+
+    #[test]
+    fn repeated_factors() {
+        assert_eq!(partial("1 / (x ^ 2 * (x + 1))"), "-1 / x + 1 / x ^ 2 + 1 / (x + 1)");
+        assert_eq!(partial("(x + 3) / (x - 1) ^ 3"), "1 / (x - 1) ^ 2 + 4 / (x - 1) ^ 3");
+    }
+}
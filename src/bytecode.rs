@@ -0,0 +1,241 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::node::*;
+use crate::simplify::simplify;
+
+use BKind::*;
+use UKind::*;
+use Node::*;
+
+// Points are evaluated in chunks so that every register of a chunk stays in cache
+// while the program runs over it.
+const CHUNK: usize = 256;
+
+// Every instruction writes the register with its own index, so operands always
+// refer to earlier registers.
+#[derive(Debug, Clone, Copy)]
+enum Op {
+    Const(f64),
+    Input(usize),
+    Binary(BKind, usize, usize),
+    Unary(UKind, usize),
+    Powi(usize, i32),
+}
+
+// `node` with the definitions of `vars` dropped, since an input shadows the
+// definition as it does in `eval`, and `simplify` would otherwise inline it.
+fn unbind(node: &Rc<Node>, vars: &[String]) -> Rc<Node> {
+    fn visit(node: &Rc<Node>, vars: &[String], memo: &mut HashMap<*const Node, Rc<Node>>) -> Rc<Node> {
+        if let Some(res) = memo.get(&Rc::as_ptr(node)) {
+            return res.clone();
+        }
+        let res = match &**node {
+            BinaryOperator { kind, lhs, rhs } => Node::binary(*kind, visit(lhs, vars, memo), visit(rhs, vars, memo)),
+            UnaryOperator { kind, operand } => Node::unary(*kind, visit(operand, vars, memo)),
+            List { items } => Node::list(items.iter().map(|item| visit(item, vars, memo)).collect()),
+            Equation { .. } | Unsolved { .. } => node.clone(),
+            Var { name, point: Some(_), .. } if vars.contains(name) => Node::var(name, None),
+            Var { point: Some(point), .. } => visit(point, vars, memo),
+            Var { .. } | Num { .. } | Const { .. } | Derivative { .. } => node.clone(),
+        };
+        memo.insert(Rc::as_ptr(node), res.clone());
+        res
+    }
+    visit(node, vars, &mut HashMap::new())
+}
+
+#[derive(Debug, Clone)]
+pub struct Program {
+    code: Vec<Op>,
+    arity: usize,
+}
+
+impl Program {
+    // Compiles `node` as a function of `vars`. Constants are folded by `simplify`
+    // and shared subexpressions of the hash-consed graph get a single register.
+    pub fn compile(node: &Rc<Node>, vars: &[String]) -> Result<Program, String> {
+        let mut program = Program { code: Vec::new(), arity: vars.len() };
+        let mut regs = HashMap::new();
+        program.emit(&simplify(&unbind(node, vars)), vars, &mut regs)?;
+        Ok(program)
+    }
+
+    fn emit(&mut self, node: &Rc<Node>, vars: &[String], regs: &mut HashMap<*const Node, usize>) -> Result<usize, String> {
+        if let Some(reg) = regs.get(&Rc::as_ptr(node)) {
+            return Ok(*reg);
+        }
+        let op = match &**node {
+            List { .. } | Equation { .. } | Unsolved { .. } => {
+                return Err(format!("{} is not a scalar expression", node));
+            },
+            BinaryOperator { kind, lhs, rhs } => {
+                let lhs = self.emit(lhs, vars, regs)?;
+                match (kind, &**rhs) {
+                    (Pow, Num { val }) if val.fract() == 0.0 && val.abs() <= 64.0 => Op::Powi(lhs, *val as i32),
+                    _ => Op::Binary(*kind, lhs, self.emit(rhs, vars, regs)?),
+                }
+            },
+            UnaryOperator { kind, operand } => Op::Unary(*kind, self.emit(operand, vars, regs)?),
+            Var { name, point, .. } => {
+                match (vars.iter().position(|var| var == name), point) {
+                    (Some(i), _) => Op::Input(i),
+                    (None, Some(point)) => return self.emit(point, vars, regs),
+                    (None, None) => return Err(format!("{} is not a parameter", name)),
+                }
+            },
+            Derivative { .. } => {
+                match vars.iter().position(|var| *var == node.to_string()) {
+                    Some(i) => Op::Input(i),
+                    None => return Err(format!("{} is not a parameter", node)),
+                }
+            },
+            Num { val } => Op::Const(*val as f64),
+            Const { kind } => match kind.value() {
+                Some(val) => Op::Const(val),
+                None => return Err(format!("{} is not a real number", kind.name())),
+            },
+        };
+        self.code.push(op);
+        regs.insert(Rc::as_ptr(node), self.code.len() - 1);
+        Ok(self.code.len() - 1)
+    }
+
+    // Evaluates the program at `inputs[0][i], inputs[1][i], ...` for every `i`,
+    // writing the results to `out[i]`.
+    pub fn run(&self, inputs: &[&[f64]], out: &mut [f64]) {
+        assert_eq!(inputs.len(), self.arity);
+        let mut regs = vec![0.0; self.code.len() * CHUNK];
+        for start in (0..out.len()).step_by(CHUNK) {
+            let len = CHUNK.min(out.len() - start);
+            self.run_chunk(inputs, start, len, &mut regs);
+            let last = (self.code.len() - 1) * CHUNK;
+            out[start..start + len].copy_from_slice(&regs[last..last + len]);
+        }
+    }
+
+    fn run_chunk(&self, inputs: &[&[f64]], start: usize, len: usize, regs: &mut [f64]) {
+        for (dest, op) in self.code.iter().enumerate() {
+            let (src, dst) = regs.split_at_mut(dest * CHUNK);
+            let dst = &mut dst[..len];
+            let reg = |i: usize| &src[i * CHUNK..i * CHUNK + len];
+            match *op {
+                Op::Const(val) => dst.fill(val),
+                Op::Input(i) => dst.copy_from_slice(&inputs[i][start..start + len]),
+                Op::Binary(kind, a, b) => {
+                    let (a, b) = (reg(a), reg(b));
+                    match kind {
+                        Add => map2(dst, a, b, |a, b| a + b),
+                        Sub => map2(dst, a, b, |a, b| a - b),
+                        Mul => map2(dst, a, b, |a, b| a * b),
+                        Div => map2(dst, a, b, |a, b| a / b),
+                        Pow => map2(dst, a, b, f64::powf),
+                    }
+                },
+                Op::Unary(kind, a) => {
+                    let a = reg(a);
+                    match kind {
+                        Neg => map1(dst, a, |a| -a),
+                        Sin => map1(dst, a, f64::sin),
+                        Cos => map1(dst, a, f64::cos),
+                        Tan => map1(dst, a, f64::tan),
+                        Exp => map1(dst, a, f64::exp),
+                        Log => map1(dst, a, f64::ln),
+                        Asin => map1(dst, a, f64::asin),
+                        Re | Conj => dst.copy_from_slice(a),
+                        Im => dst.fill(0.0),
+                        Arg => map1(dst, a, |a| f64::atan2(0.0, a)),
+                        Abs => map1(dst, a, f64::abs),
+                    }
+                },
+                Op::Powi(a, n) => map1(dst, reg(a), |a| a.powi(n)),
+            }
+        }
+    }
+
+    // Evaluates the program at a single point.
+    pub fn at(&self, point: &[f64]) -> f64 {
+        let inputs = point.iter().map(std::slice::from_ref).collect::<Vec<&[f64]>>();
+        let mut out = [0.0];
+        self.run(&inputs, &mut out);
+        out[0]
+    }
+}
+
+fn map1(dst: &mut [f64], a: &[f64], f: impl Fn(f64) -> f64) {
+    for (dst, a) in dst.iter_mut().zip(a) {
+        *dst = f(*a);
+    }
+}
+
+fn map2(dst: &mut [f64], a: &[f64], b: &[f64], f: impl Fn(f64, f64) -> f64) {
+    for (dst, (a, b)) in dst.iter_mut().zip(a.iter().zip(b)) {
+        *dst = f(*a, *b);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::{eval, Env};
+    use crate::parser::parse;
+    use std::time::Instant;
+
+    fn grid(count: usize, vars: usize) -> Vec<Vec<f64>> {
+        (0..vars).map(|j| (0..count).map(|i| 1.0 + ((i * (2 * j + 1)) % 1000) as f64 / 1000.0).collect()).collect()
+    }
+
+    // Evaluates `node` at every point by walking the graph, reusing one environment.
+    fn walk(node: &Rc<Node>, names: &[String], columns: &[Vec<f64>]) -> Vec<f64> {
+        let mut env = names.iter().map(|name| (name.clone(), 0.0)).collect::<Env<f64>>();
+        (0..columns[0].len()).map(|i| {
+            for (name, column) in names.iter().zip(columns) {
+                *env.get_mut(name).unwrap() = column[i];
+            }
+            eval(node, &env).unwrap()
+        }).collect()
+    }
+
+    fn run(program: &Program, columns: &[Vec<f64>]) -> Vec<f64> {
+        let inputs = columns.iter().map(|column| column.as_slice()).collect::<Vec<&[f64]>>();
+        let mut out = vec![0.0; columns[0].len()];
+        program.run(&inputs, &mut out);
+        out
+    }
+
+    #[test]
+    fn agrees_with_tree_walk() {
+        let node = parse("var x, y; dif(dif(x ^ x * sin(x * y) / (1 + y ^ 2), x), y) + abs(log(x) - y) ^ 3");
+        let names = ["x", "y"].map(String::from);
+        let columns = grid(1000, 2);
+        let program = Program::compile(&node, &names).unwrap();
+        let compiled = run(&program, &columns);
+        for (walked, compiled) in walk(&node, &names, &columns).iter().zip(&compiled) {
+            assert!((walked - compiled).abs() <= 1e-9 * walked.abs().max(1.0), "{} != {}", walked, compiled);
+        }
+        assert_eq!(program.at(&[columns[0][7], columns[1][7]]), compiled[7]);
+    }
+
+    // Run with `cargo test --release -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn faster_than_tree_walk() {
+        let node = parse("var x, y; var f = x ^ x * sin(x * y); dif(dif(dif(f, x), x), y)");
+        let names = ["x", "y"].map(String::from);
+        let columns = grid(100_000, 2);
+        let program = Program::compile(&node, &names).unwrap();
+
+        let start = Instant::now();
+        let walked = walk(&node, &names, &columns);
+        let walk_time = start.elapsed();
+        let start = Instant::now();
+        let compiled = run(&program, &columns);
+        let run_time = start.elapsed();
+
+        let error = walked.iter().zip(&compiled).map(|(a, b)| (a - b).abs()).fold(0.0, f64::max);
+        println!("tree walk: {:.3} ms", walk_time.as_secs_f64() * 1e3);
+        println!("bytecode: {:.3} ms ({} instructions)", run_time.as_secs_f64() * 1e3, program.code.len());
+        println!("speedup: {:.1}x, max difference: {:e}", walk_time.as_secs_f64() / run_time.as_secs_f64(), error);
+        assert!(run_time < walk_time);
+    }
+}
@@ -0,0 +1,445 @@
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use crate::cse;
+use crate::node::*;
+use crate::simplify::simplify;
+
+use BKind::*;
+use UKind::*;
+use Node::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Lang {
+    C,
+    Rust,
+    NumPy,
+    SymPy,
+}
+
+impl Lang {
+    pub fn from_name(name: &str) -> Option<Lang> {
+        match name {
+            "c" => Some(Lang::C),
+            "rust" => Some(Lang::Rust),
+            "numpy" => Some(Lang::NumPy),
+            "sympy" => Some(Lang::SymPy),
+            _ => None,
+        }
+    }
+
+    fn call(&self, kind: UKind) -> &'static str {
+        match (self, kind) {
+            (_, Neg) => "-",
+            (Lang::C, Sin) => "sin",
+            (Lang::C, Cos) => "cos",
+            (Lang::C, Tan) => "tan",
+            (Lang::C, Exp) => "exp",
+            (Lang::C, Log) => "log",
+            (Lang::C, Asin) => "asin",
+            (Lang::C, Abs) => "fabs",
+            (Lang::Rust, Sin) => "f64::sin",
+            (Lang::Rust, Cos) => "f64::cos",
+            (Lang::Rust, Tan) => "f64::tan",
+            (Lang::Rust, Exp) => "f64::exp",
+            (Lang::Rust, Log) => "f64::ln",
+            (Lang::Rust, Asin) => "f64::asin",
+            (Lang::Rust, Abs) => "f64::abs",
+            // Written out by `expr`, since C and Rust functions take real arguments.
+            (Lang::C | Lang::Rust, Re | Im | Conj | Arg) => unreachable!(),
+            (Lang::NumPy, Sin) => "np.sin",
+            (Lang::NumPy, Cos) => "np.cos",
+            (Lang::NumPy, Tan) => "np.tan",
+            (Lang::NumPy, Exp) => "np.exp",
+            (Lang::NumPy, Log) => "np.log",
+            (Lang::NumPy, Asin) => "np.arcsin",
+            (Lang::NumPy, Re) => "np.real",
+            (Lang::NumPy, Im) => "np.imag",
+            (Lang::NumPy, Conj) => "np.conj",
+            (Lang::NumPy, Arg) => "np.angle",
+            (Lang::NumPy, Abs) => "np.abs",
+            (Lang::SymPy, Sin) => "sympy.sin",
+            (Lang::SymPy, Cos) => "sympy.cos",
+            (Lang::SymPy, Tan) => "sympy.tan",
+            (Lang::SymPy, Exp) => "sympy.exp",
+            (Lang::SymPy, Log) => "sympy.log",
+            (Lang::SymPy, Asin) => "sympy.asin",
+            (Lang::SymPy, Re) => "sympy.re",
+            (Lang::SymPy, Im) => "sympy.im",
+            (Lang::SymPy, Conj) => "sympy.conjugate",
+            (Lang::SymPy, Arg) => "sympy.arg",
+            (Lang::SymPy, Abs) => "sympy.Abs",
+        }
+    }
+
+    fn sqrt(&self) -> &'static str {
+        match self {
+            Lang::C => "sqrt",
+            Lang::Rust => "f64::sqrt",
+            Lang::NumPy => "np.sqrt",
+            Lang::SymPy => "sympy.sqrt",
+        }
+    }
+
+    fn constant(&self, kind: CKind) -> &'static str {
+        match (self, kind) {
+            (Lang::C, CKind::Pi) => "M_PI",
+            (Lang::C, CKind::E) => "M_E",
+            (Lang::C, CKind::Inf) => "INFINITY",
+            (Lang::Rust, CKind::Pi) => "std::f64::consts::PI",
+            (Lang::Rust, CKind::E) => "std::f64::consts::E",
+            (Lang::Rust, CKind::Inf) => "f64::INFINITY",
+            (Lang::NumPy, CKind::Pi) => "np.pi",
+            (Lang::NumPy, CKind::E) => "np.e",
+            (Lang::NumPy, CKind::Inf) => "np.inf",
+            (Lang::SymPy, CKind::Pi) => "sympy.pi",
+            (Lang::SymPy, CKind::E) => "sympy.E",
+            (Lang::SymPy, CKind::Inf) => "sympy.oo",
+            (Lang::SymPy, CKind::I) => "sympy.I",
+            // Rejected by `check_scalar`.
+            (_, CKind::I) => "1j",
+        }
+    }
+
+    fn is_python(&self) -> bool {
+        matches!(self, Lang::NumPy | Lang::SymPy)
+    }
+
+    // Python binds `**` tighter than negation, so negation sits one level lower.
+    fn neg_level(&self) -> usize {
+        if self.is_python() { 3 } else { 4 }
+    }
+
+    fn num(&self, val: f32) -> String {
+        let text = val.to_string();
+        if text.contains('.') || self.is_python() { text } else { format!("{}.0", text) }
+    }
+}
+
+// Generated expressions use the same binding strengths as `Node::level`: 1 for
+// sums, 2 for products, 3 and 4 for negation and powers in the order the target
+// language binds them, and 5 for atoms and calls.
+fn wrap((text, level): (String, usize), required: usize) -> String {
+    if level < required { format!("({})", text) } else { text }
+}
+
+fn expr(lang: Lang, constant: &mut Constant, node: &Node) -> (String, usize) {
+    match node {
+        BinaryOperator { kind, lhs, rhs } => {
+            let (symbol, level) = match kind {
+                Add => ("+", 1),
+                Sub => ("-", 1),
+                Mul => ("*", 2),
+                Div => ("/", 2),
+                Pow if lang.is_python() => {
+                    let base = wrap(sympy_exact(lang, constant, lhs, rhs), 5);
+                    return (format!("{} ** {}", base, wrap(expr(lang, constant, rhs), 3)), 4);
+                },
+                Pow => return pow(lang, constant, lhs, rhs),
+            };
+            let lhs = match kind {
+                Div => wrap(sympy_exact(lang, constant, lhs, rhs), level),
+                _ => wrap(expr(lang, constant, lhs), level),
+            };
+            let rhs = wrap(expr(lang, constant, rhs), level + 1);
+            (format!("{} {} {}", lhs, symbol, rhs), level)
+        },
+        UnaryOperator { kind: Neg, operand } => {
+            (format!("-{}", wrap(expr(lang, constant, operand), lang.neg_level() + 1)), lang.neg_level())
+        },
+        // `check_scalar` keeps `i` out of C and Rust, so every operand is real.
+        UnaryOperator { kind: Re | Conj, operand } if !lang.is_python() => expr(lang, constant, operand),
+        UnaryOperator { kind: Im, .. } if !lang.is_python() => (lang.num(0.0), 5),
+        UnaryOperator { kind: Arg, operand } if !lang.is_python() => {
+            let call = if lang == Lang::Rust { "f64::atan2" } else { "atan2" };
+            (format!("{}({}, {})", call, lang.num(0.0), expr(lang, constant, operand).0), 5)
+        },
+        UnaryOperator { kind, operand } => (format!("{}({})", lang.call(*kind), expr(lang, constant, operand).0), 5),
+        Var { point: Some(point), .. } => expr(lang, constant, point),
+        Var { name, point: None, deps } if lang == Lang::SymPy && !deps.is_empty() => {
+            let args = deps.iter().map(|dep| format!("sympy.Symbol('{}')", dep)).collect::<Vec<String>>();
+            (format!("sympy.Function('{}')({})", name, args.join(", ")), 5)
+        },
+        Var { name, point: None, .. } if lang == Lang::SymPy => (format!("sympy.Symbol('{}')", name), 5),
+        Derivative { of, var } if lang == Lang::SymPy => {
+            (format!("sympy.Derivative({}, {})", expr(lang, constant, of).0, expr(lang, constant, var).0), 5)
+        },
+        // Rejected by `check_scalar`.
+        Derivative { .. } => (node.to_string(), 5),
+        Var { name, point: None, .. } => (name.clone(), 5),
+        Num { val } if val.is_sign_negative() => (lang.num(*val), lang.neg_level()),
+        Num { val } => (lang.num(*val), 5),
+        Const { kind } => (lang.constant(*kind).to_string(), 5),
+        List { items } => {
+            let items = items.iter().map(|item| expr(lang, constant, item).0).collect::<Vec<String>>();
+            (format!("[{}]", items.join(", ")), 5)
+        },
+        // Only SymPy has values for these; `function` rejects them elsewhere.
+        Equation { lhs, rhs } if lang == Lang::SymPy => {
+            (format!("sympy.Eq({}, {})", expr(lang, constant, lhs).0, expr(lang, constant, rhs).0), 5)
+        },
+        Unsolved { equation, var } if lang == Lang::SymPy => {
+            (format!("sympy.solve({}, {})", expr(lang, constant, equation).0, expr(lang, constant, var).0), 5)
+        },
+        Equation { .. } | Unsolved { .. } => (node.to_string(), 0),
+    }
+}
+
+// Whether each node visited so far is free of variables, so that printing a
+// DAG as a tree looks at every node's variables once.
+type Constant = HashMap<*const Node, bool>;
+
+fn is_constant(node: &Node, constant: &mut Constant) -> bool {
+    if let Some(&known) = constant.get(&(node as *const Node)) {
+        return known;
+    }
+    let result = match node {
+        BinaryOperator { lhs, rhs, .. } | Equation { lhs, rhs } => is_constant(lhs, constant) && is_constant(rhs, constant),
+        UnaryOperator { operand, .. } => is_constant(operand, constant),
+        Var { point: Some(point), .. } => is_constant(point, constant),
+        // An unsolved equation is kept symbolic either way.
+        Var { point: None, .. } | Derivative { .. } | Unsolved { .. } => false,
+        List { items } => items.iter().all(|item| is_constant(item, constant)),
+        Num { .. } | Const { .. } => true,
+    };
+    constant.insert(node as *const Node, result);
+    result
+}
+
+// Python evaluates `1 / 2` and `2 ** -1` to floats, so when both operands are
+// constant the left one is made a SymPy number to keep the result exact.
+fn sympy_exact(lang: Lang, constant: &mut Constant, lhs: &Rc<Node>, rhs: &Rc<Node>) -> (String, usize) {
+    if lang == Lang::SymPy && is_constant(lhs, constant) && is_constant(rhs, constant) {
+        (format!("sympy.S({})", expr(lang, constant, lhs).0), 5)
+    } else {
+        expr(lang, constant, lhs)
+    }
+}
+
+// Square roots and reciprocals have dedicated forms. In C small integer powers are
+// cheaper as products, while Rust has `powi` for any integer exponent.
+fn pow(lang: Lang, constant: &mut Constant, base: &Node, exponent: &Node) -> (String, usize) {
+    if let Num { val } = exponent {
+        let n = *val;
+        if n == 1.0 {
+            return expr(lang, constant, base);
+        }
+        if n == 0.5 {
+            return (format!("{}({})", lang.sqrt(), expr(lang, constant, base).0), 5);
+        }
+        if n == -1.0 {
+            return (format!("{} / {}", lang.num(1.0), wrap(expr(lang, constant, base), 3)), 2);
+        }
+        if n.fract() == 0.0 {
+            match lang {
+                Lang::C if (2.0..=4.0).contains(&n) => {
+                    let factor = wrap(expr(lang, constant, base), 3);
+                    return (vec![factor; n as usize].join(" * "), 2);
+                },
+                Lang::Rust if n.abs() <= i32::MAX as f32 => {
+                    return (format!("f64::powi({}, {})", expr(lang, constant, base).0, n as i32), 5);
+                },
+                _ => {},
+            }
+        }
+    }
+    let call = match lang {
+        Lang::Rust => "f64::powf",
+        _ => "pow",
+    };
+    (format!("{}({}, {})", call, expr(lang, constant, base).0, expr(lang, constant, exponent).0), 5)
+}
+
+fn check_scalar(node: &Rc<Node>, seen: &mut HashSet<*const Node>) -> Result<(), String> {
+    if !seen.insert(Rc::as_ptr(node)) {
+        return Ok(());
+    }
+    match &**node {
+        List { .. } | Equation { .. } | Unsolved { .. } => Err(format!("{} is not a scalar expression", node)),
+        BinaryOperator { lhs, rhs, .. } => {
+            check_scalar(lhs, seen)?;
+            check_scalar(rhs, seen)
+        },
+        UnaryOperator { operand, .. } => check_scalar(operand, seen),
+        Var { point: Some(point), .. } => check_scalar(point, seen),
+        Const { kind: CKind::I } => Err("i is not a real number".to_string()),
+        Derivative { .. } => Err(format!("{} has no numeric value", node)),
+        Var { point: None, .. } | Num { .. } | Const { .. } => Ok(()),
+    }
+}
+
+fn check_vars(cse: &cse::Cse, params: &[String]) -> Result<(), String> {
+    let mut known = params.to_vec();
+    for (name, temp) in &cse.temps {
+        check_node(temp, &known)?;
+        known.push(name.clone());
+    }
+    for root in &cse.roots {
+        check_node(root, &known)?;
+    }
+    Ok(())
+}
+
+fn check_node(node: &Rc<Node>, known: &[String]) -> Result<(), String> {
+    match Node::free_vars(node).into_iter().find(|name| !known.contains(name)) {
+        Some(name) => Err(format!("{} is not a parameter", name)),
+        None => Ok(()),
+    }
+}
+
+// Rust parameters, with those the code never reads named `_x` so that the
+// function compiles without warnings.
+fn rust_args(cse: &cse::Cse, params: &[String]) -> String {
+    let nodes = cse.temps.iter().map(|(_, temp)| temp).chain(&cse.roots);
+    let used = nodes.flat_map(Node::free_vars).collect::<Vec<String>>();
+    params.iter().map(|param| {
+        let prefix = if used.contains(param) { "" } else { "_" };
+        format!("{}{}: f64", prefix, param)
+    }).collect::<Vec<String>>().join(", ")
+}
+
+fn body(lang: Lang, constant: &mut Constant, cse: &cse::Cse, code: &mut String) {
+    for (name, temp) in &cse.temps {
+        match lang {
+            Lang::C => code.push_str(&format!("    const double {} = {};\n", name, expr(lang, constant, temp).0)),
+            Lang::Rust => code.push_str(&format!("    let {} = {};\n", name, expr(lang, constant, temp).0)),
+            Lang::NumPy | Lang::SymPy => code.push_str(&format!("    {} = {}\n", name, expr(lang, constant, temp).0)),
+        }
+    }
+}
+
+// SymPy expressions name their symbols inline and simplify themselves with exact
+// rationals, so they are kept whole rather than folded and split into temporaries.
+fn prepare(lang: Lang, nodes: Vec<Rc<Node>>) -> cse::Cse {
+    match lang {
+        Lang::SymPy => cse::Cse { temps: Vec::new(), roots: nodes },
+        _ => cse::eliminate(&nodes.iter().map(simplify).collect::<Vec<Rc<Node>>>()),
+    }
+}
+
+// Renders `node` as a single expression of `lang`.
+pub fn expression(lang: Lang, node: &Rc<Node>) -> String {
+    expr(lang, &mut Constant::new(), node).0
+}
+
+// Emits a function computing `node` from `params`, optionally followed by one
+// computing its gradient with respect to all of them. For SymPy the "function" is
+// the expression itself, assigned to `name`.
+pub fn function(lang: Lang, name: &str, params: &[String], node: &Rc<Node>, gradient: bool) -> Result<String, String> {
+    // Simplified first, so that `i` may appear where it cancels.
+    if lang != Lang::SymPy {
+        check_scalar(&simplify(node), &mut HashSet::new())?;
+    }
+    let mut code = String::new();
+    let mut constant = Constant::new();
+    let value = prepare(lang, vec![node.clone()]);
+    check_vars(&value, params)?;
+
+    match lang {
+        Lang::C => {
+            let args = params.iter().map(|param| format!("double {}", param)).collect::<Vec<String>>().join(", ");
+            code.push_str("#include <math.h>\n\n");
+            code.push_str(&format!("double {}({}) {{\n", name, args));
+            body(lang, &mut constant, &value, &mut code);
+            code.push_str(&format!("    return {};\n}}\n", expr(lang, &mut constant, &value.roots[0]).0));
+        },
+        Lang::Rust => {
+            code.push_str(&format!("pub fn {}({}) -> f64 {{\n", name, rust_args(&value, params)));
+            body(lang, &mut constant, &value, &mut code);
+            code.push_str(&format!("    {}\n}}\n", expr(lang, &mut constant, &value.roots[0]).0));
+        },
+        Lang::NumPy => {
+            code.push_str("import numpy as np\n\n");
+            code.push_str(&format!("def {}({}):\n", name, params.join(", ")));
+            body(lang, &mut constant, &value, &mut code);
+            code.push_str(&format!("    return {}\n", expr(lang, &mut constant, &value.roots[0]).0));
+        },
+        Lang::SymPy => {
+            code.push_str("import sympy\n\n");
+            code.push_str(&format!("{} = {}\n", name, expr(lang, &mut constant, &value.roots[0]).0));
+        },
+    }
+
+    if gradient {
+        let partials = params.iter().map(|param| Node::dif(node, &Node::var(param, None))).collect::<Vec<Rc<Node>>>();
+        let grad = prepare(lang, partials);
+        check_vars(&grad, params)?;
+
+        match lang {
+            Lang::C => {
+                let args = params.iter().map(|param| format!("double {}", param)).collect::<Vec<String>>().join(", ");
+                code.push_str(&format!("\nvoid {}_grad({}, double grad[{}]) {{\n", name, args, params.len()));
+                body(lang, &mut constant, &grad, &mut code);
+                for (i, partial) in grad.roots.iter().enumerate() {
+                    code.push_str(&format!("    grad[{}] = {};\n", i, expr(lang, &mut constant, partial).0));
+                }
+                code.push_str("}\n");
+            },
+            Lang::Rust => {
+                code.push_str(&format!("\npub fn {}_grad({}) -> [f64; {}] {{\n", name, rust_args(&grad, params), params.len()));
+                body(lang, &mut constant, &grad, &mut code);
+                let partials = grad.roots.iter().map(|partial| expr(lang, &mut constant, partial).0).collect::<Vec<String>>();
+                code.push_str(&format!("    [{}]\n}}\n", partials.join(", ")));
+            },
+            Lang::NumPy => {
+                code.push_str(&format!("\ndef {}_grad({}):\n", name, params.join(", ")));
+                body(lang, &mut constant, &grad, &mut code);
+                let partials = grad.roots.iter().map(|partial| expr(lang, &mut constant, partial).0).collect::<Vec<String>>();
+                code.push_str(&format!("    return [{}]\n", partials.join(", ")));
+            },
+            Lang::SymPy => {
+                let partials = grad.roots.iter().map(|partial| expr(lang, &mut constant, partial).0).collect::<Vec<String>>();
+                code.push_str(&format!("{}_grad = [{}]\n", name, partials.join(", ")));
+            },
+        }
+    }
+    Ok(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    #[test]
+    fn rust_function_and_gradient_compile() {
+        let (x, y, z) = (Node::var("x", None), Node::var("y", None), Node::var("z", None));
+        let shared = Node::binary(Mul, x.clone(), y.clone());
+        let node = [
+            Node::binary(Div, Node::unary(Sin, shared.clone()), Node::binary(Add, Node::num(1.0), Node::binary(Pow, x.clone(), Node::num(2.0)))),
+            Node::binary(Pow, Node::unary(Exp, shared.clone()), Node::num(0.5)),
+            Node::binary(Pow, Node::unary(Log, y.clone()), x.clone()),
+            Node::unary(Abs, Node::binary(Sub, x.clone(), Node::constant(CKind::Pi))),
+            Node::binary(Mul, Node::unary(Tan, z.clone()), Node::binary(Pow, y.clone(), Node::num(-3.0))),
+        ].into_iter().reduce(|sum, term| Node::binary(Add, sum, term)).unwrap();
+        let params = ["x", "y", "z", "w"].map(String::from);
+        let code = function(Lang::Rust, "f", &params, &node, true).unwrap();
+
+        let dir = std::env::temp_dir().join(format!("sigma-codegen-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("f.rs");
+        std::fs::write(&path, &code).unwrap();
+        let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+        let output = Command::new(rustc)
+            .args(["--crate-type=lib", "--edition=2021", "-D", "warnings", "--out-dir"])
+            .arg(&dir)
+            .arg(&path)
+            .output()
+            .unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(output.status.success(), "{}\n{}", code, String::from_utf8_lossy(&output.stderr));
+    }
+
+    #[test]
+    fn shared_subexpressions_are_checked_once() {
+        // As a tree this has 2^64 leaves.
+        let mut node = Node::var("x", None);
+        for _ in 0..64 {
+            node = Node::binary(Mul, Node::unary(Sin, node.clone()), Node::unary(Cos, node));
+        }
+        assert!(check_scalar(&node, &mut HashSet::new()).is_ok());
+        assert!(!is_constant(&node, &mut Constant::new()));
+        let code = function(Lang::C, "f", &["x".to_string()], &node, false).unwrap();
+        assert_eq!(code.matches("sin(").count(), 64);
+    }
+}
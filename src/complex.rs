@@ -0,0 +1,185 @@
+use std::fmt;
+use std::ops::{Add, Sub, Mul, Div, Neg};
+use std::rc::Rc;
+
+use crate::eval::*;
+use crate::node::*;
+
+// `re + im i`. Functions take their principal branches, cut along the negative
+// real axis for `log` and fractional powers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Complex {
+    re: f64,
+    im: f64,
+}
+
+impl Complex {
+    fn real(re: f64) -> Complex {
+        Complex { re, im: 0.0 }
+    }
+
+    fn polar(r: f64, theta: f64) -> Complex {
+        Complex { re: r * theta.cos(), im: r * theta.sin() }
+    }
+
+    fn norm(self) -> f64 {
+        self.re.hypot(self.im)
+    }
+
+    // In `(-pi, pi]`; adding zero turns the `-0` that negating a real leaves
+    // behind into `0`, so that `-1` sits above the cut.
+    fn angle(self) -> f64 {
+        (self.im + 0.0).atan2(self.re)
+    }
+
+    fn sqrt(self) -> Complex {
+        Complex::polar(self.norm().sqrt(), 0.5 * self.angle())
+    }
+
+    // Integer powers by repeated squaring, which keeps `i ^ 2` exactly `-1`.
+    fn powi(self, n: i64) -> Complex {
+        let mut res = Complex::real(1.0);
+        let mut base = if n < 0 { Complex::real(1.0) / self } else { self };
+        let mut n = n.unsigned_abs();
+        while n > 0 {
+            if n & 1 == 1 {
+                res = res * base;
+            }
+            base = base * base;
+            n >>= 1;
+        }
+        res
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+    fn add(self, rhs: Complex) -> Complex { Complex { re: self.re + rhs.re, im: self.im + rhs.im } }
+}
+
+impl Sub for Complex {
+    type Output = Complex;
+    fn sub(self, rhs: Complex) -> Complex { Complex { re: self.re - rhs.re, im: self.im - rhs.im } }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+    fn mul(self, rhs: Complex) -> Complex {
+        Complex { re: self.re * rhs.re - self.im * rhs.im, im: self.re * rhs.im + self.im * rhs.re }
+    }
+}
+
+impl Div for Complex {
+    type Output = Complex;
+    fn div(self, rhs: Complex) -> Complex {
+        let den = rhs.re * rhs.re + rhs.im * rhs.im;
+        Complex {
+            re: (self.re * rhs.re + self.im * rhs.im) / den,
+            im: (self.im * rhs.re - self.re * rhs.im) / den,
+        }
+    }
+}
+
+impl Neg for Complex {
+    type Output = Complex;
+    fn neg(self) -> Complex { Complex { re: -self.re, im: -self.im } }
+}
+
+impl Scalar for Complex {
+    fn constant(val: f64) -> Self { Complex::real(val) }
+    fn imaginary() -> Option<Self> { Some(Complex { re: 0.0, im: 1.0 }) }
+    fn sin(self) -> Self {
+        Complex { re: self.re.sin() * self.im.cosh(), im: self.re.cos() * self.im.sinh() }
+    }
+    fn cos(self) -> Self {
+        Complex { re: self.re.cos() * self.im.cosh(), im: -self.re.sin() * self.im.sinh() }
+    }
+    fn tan(self) -> Self { self.sin() / self.cos() }
+    fn exp(self) -> Self { Complex::polar(self.re.exp(), self.im) }
+    fn log(self) -> Self { Complex { re: self.norm().ln(), im: self.angle() } }
+    // `asin(z) = -i log(i z + sqrt(1 - z^2))`.
+    fn asin(self) -> Self {
+        let i = Complex { re: 0.0, im: 1.0 };
+        -i * (i * self + (Complex::real(1.0) - self * self).sqrt()).log()
+    }
+    fn pow(self, exponent: Self) -> Self {
+        // Real powers stay exact where they are real.
+        if self.im == 0.0 && exponent.im == 0.0 && (self.re >= 0.0 || exponent.re.fract() == 0.0) {
+            return Complex::real(self.re.powf(exponent.re));
+        }
+        if exponent.im == 0.0 && exponent.re.fract() == 0.0 && exponent.re.abs() <= 64.0 {
+            return self.powi(exponent.re as i64);
+        }
+        if self.re == 0.0 && self.im == 0.0 {
+            return Complex::real(if exponent.re > 0.0 { 0.0 } else { f64::NAN });
+        }
+        (exponent * self.log()).exp()
+    }
+    fn re(self) -> Self { Complex::real(self.re) }
+    fn im(self) -> Self { Complex::real(self.im) }
+    fn conj(self) -> Self { Complex { re: self.re, im: -self.im } }
+    fn arg(self) -> Self { Complex::real(self.angle()) }
+    fn abs(self) -> Self { Complex::real(self.norm()) }
+}
+
+impl fmt::Display for Complex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.re, self.im) {
+            (re, 0.0) => write!(f, "{}", re),
+            (0.0, im) => write!(f, "{}i", im),
+            (re, im) if im < 0.0 => write!(f, "{} - {}i", re, -im),
+            (re, im) => write!(f, "{} + {}i", re, im),
+        }
+    }
+}
+
+// Value of `node` at `point`, whose coordinates may themselves be complex.
+pub fn value(node: &Rc<Node>, vars: &[Rc<Node>], point: &[Rc<Node>]) -> Result<Complex, String> {
+    let mut env = Env::new();
+    for (var, val) in vars.iter().zip(point) {
+        match &**var {
+            Node::Var { name, .. } => {
+                env.insert(name.clone(), eval(val, &Env::new())?);
+            },
+            _ => return Err("expected a variable".to_string()),
+        }
+    }
+    eval(node, &env)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    fn at(src: &str) -> Complex {
+        value(&parse(src), &[], &[]).unwrap()
+    }
+
+    fn close(z: Complex, re: f64, im: f64) -> bool {
+        (z.re - re).abs() < 1e-12 && (z.im - im).abs() < 1e-12
+    }
+
+    #[test]
+    fn principal_values() {
+        assert_eq!(at("i ^ 2").to_string(), "-1");
+        assert_eq!(at("abs(3 + 4 * i)").to_string(), "5");
+        assert_eq!(at("conj(1 + 2 * i)").to_string(), "1 - 2i");
+        assert_eq!(at("1 / (1 + i)").to_string(), "0.5 - 0.5i");
+        assert!(close(at("(-4) ^ 0.5"), 0.0, 2.0));
+        // On the cut, `log` and roots take the upper side.
+        assert!(close(at("log(-1)"), 0.0, std::f64::consts::PI));
+        assert!(close(at("exp(i * pi)"), -1.0, 0.0));
+        assert!(close(at("asin(2)"), std::f64::consts::FRAC_PI_2, -(2.0 + 3f64.sqrt()).ln()));
+    }
+
+    #[test]
+    fn values_at_complex_points() {
+        let (vars, point) = (parse("var z; [z]"), parse("[i]"));
+        let vars = crate::matrix::items(&vars).unwrap();
+        let point = crate::matrix::items(&point).unwrap();
+        assert_eq!(value(&parse("var z; z ^ 2 + 1"), &vars, &point).unwrap(), Complex::real(0.0));
+        let sin = value(&parse("var z; asin(sin(z))"), &vars, &point).unwrap();
+        assert!(sin.re.abs() < 1e-12 && (sin.im - 1.0).abs() < 1e-12);
+    }
+}
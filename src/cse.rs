@@ -0,0 +1,115 @@
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use crate::node::*;
+
+use Node::*;
+
+// Expressions split into temporaries, each used more than once, and the
+// expressions that remain once they are substituted by their names. Temporaries
+// are listed in dependency order so that each only refers to earlier ones.
+pub struct Cse {
+    pub temps: Vec<(String, Rc<Node>)>,
+    pub roots: Vec<Rc<Node>>,
+}
+
+struct Uses {
+    count: HashMap<*const Node, usize>,
+    order: Vec<Rc<Node>>,
+    names: HashSet<String>,
+}
+
+impl Uses {
+    // Counts one more use of `node`, visiting its children on the first one.
+    fn visit(&mut self, node: &Rc<Node>) {
+        let count = self.count.entry(Rc::as_ptr(node)).or_insert(0);
+        *count += 1;
+        if *count > 1 {
+            return;
+        }
+        match &**node {
+            BinaryOperator { lhs, rhs, .. } | Equation { lhs, rhs } => {
+                self.visit(lhs);
+                self.visit(rhs);
+            },
+            UnaryOperator { operand, .. } => {
+                self.visit(operand);
+            },
+            Var { name, .. } => {
+                self.names.insert(name.clone());
+            },
+            // The variable solved for is bound inside, so it is left whole.
+            Num { .. } | Const { .. } | Derivative { .. } | Unsolved { .. } => {},
+            List { items } => {
+                for item in items {
+                    self.visit(item);
+                }
+            },
+        }
+        self.order.push(node.clone());
+    }
+}
+
+// Whether a repeated node is worth naming; repeating `x` or `-x` costs nothing.
+fn worth_naming(node: &Node) -> bool {
+    match node {
+        BinaryOperator { .. } | Unsolved { .. } => true,
+        UnaryOperator { kind: UKind::Neg, operand } => worth_naming(operand),
+        UnaryOperator { .. } => true,
+        Var { .. } | Num { .. } | Const { .. } | List { .. } | Derivative { .. } | Equation { .. } => false,
+    }
+}
+
+pub fn eliminate(nodes: &[Rc<Node>]) -> Cse {
+    let nodes = nodes.iter().map(Node::resolve).collect::<Vec<Rc<Node>>>();
+    let mut uses = Uses { count: HashMap::new(), order: Vec::new(), names: HashSet::new() };
+    for node in &nodes {
+        uses.visit(node);
+    }
+
+    let mut temps = Vec::new();
+    let mut replaced: HashMap<*const Node, Rc<Node>> = HashMap::new();
+    let mut next = 1;
+    for node in &uses.order {
+        let rebuilt = match &**node {
+            BinaryOperator { kind, lhs, rhs } => {
+                Node::binary(*kind, replaced[&Rc::as_ptr(lhs)].clone(), replaced[&Rc::as_ptr(rhs)].clone())
+            },
+            UnaryOperator { kind, operand } => Node::unary(*kind, replaced[&Rc::as_ptr(operand)].clone()),
+            Var { .. } | Num { .. } | Const { .. } | Derivative { .. } | Unsolved { .. } => node.clone(),
+            List { items } => Node::list(items.iter().map(|item| replaced[&Rc::as_ptr(item)].clone()).collect()),
+            Equation { lhs, rhs } => Node::equation(replaced[&Rc::as_ptr(lhs)].clone(), replaced[&Rc::as_ptr(rhs)].clone()),
+        };
+        let count = uses.count.get(&Rc::as_ptr(node)).copied().unwrap_or(0);
+        if count >= 2 && worth_naming(node) {
+            let mut name = format!("t{}", next);
+            while uses.names.contains(&name) {
+                next += 1;
+                name = format!("t{}", next);
+            }
+            next += 1;
+            replaced.insert(Rc::as_ptr(node), Node::var(&name, None));
+            temps.push((name, rebuilt));
+        } else {
+            replaced.insert(Rc::as_ptr(node), rebuilt);
+        }
+    }
+
+    let roots = nodes.iter().map(|node| replaced[&Rc::as_ptr(node)].clone()).collect();
+    Cse { temps, roots }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    #[test]
+    fn repeated_subexpressions_become_temporaries() {
+        let cse = eliminate(&[parse("var x, y; sin(x * y) + cos(x * y) * sin(x * y)"), parse("var x, y; 2 * x * y")]);
+        let temps = cse.temps.iter().map(|(name, temp)| format!("{} = {}", name, temp)).collect::<Vec<String>>();
+        let roots = cse.roots.iter().map(|root| root.to_string()).collect::<Vec<String>>();
+        assert_eq!(temps, ["t1 = x * y", "t2 = sin(t1)"]);
+        assert_eq!(roots, ["t2 + cos(t1) * t2", "2 * x * y"]);
+    }
+}
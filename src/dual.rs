@@ -0,0 +1,208 @@
+use std::ops::{Add, Sub, Mul, Div, Neg};
+use std::rc::Rc;
+
+use crate::eval::*;
+use crate::node::*;
+
+// `re + eps ε` with ε^2 = 0. Evaluating with `eps` seeded to a direction gives the
+// directional derivative in `eps` of the result.
+#[derive(Debug, Clone, Copy)]
+pub struct Dual {
+    re: f64,
+    eps: f64,
+}
+
+impl Dual {
+    fn chain(self, f: f64, df: f64) -> Dual {
+        Dual { re: f, eps: df * self.eps }
+    }
+}
+
+impl Add for Dual {
+    type Output = Dual;
+    fn add(self, rhs: Dual) -> Dual { Dual { re: self.re + rhs.re, eps: self.eps + rhs.eps } }
+}
+
+impl Sub for Dual {
+    type Output = Dual;
+    fn sub(self, rhs: Dual) -> Dual { Dual { re: self.re - rhs.re, eps: self.eps - rhs.eps } }
+}
+
+impl Mul for Dual {
+    type Output = Dual;
+    fn mul(self, rhs: Dual) -> Dual {
+        Dual { re: self.re * rhs.re, eps: self.re * rhs.eps + self.eps * rhs.re }
+    }
+}
+
+impl Div for Dual {
+    type Output = Dual;
+    fn div(self, rhs: Dual) -> Dual {
+        Dual { re: self.re / rhs.re, eps: (self.eps * rhs.re - self.re * rhs.eps) / (rhs.re * rhs.re) }
+    }
+}
+
+impl Neg for Dual {
+    type Output = Dual;
+    fn neg(self) -> Dual { Dual { re: -self.re, eps: -self.eps } }
+}
+
+impl Scalar for Dual {
+    fn constant(val: f64) -> Self { Dual { re: val, eps: 0.0 } }
+    fn imaginary() -> Option<Self> { None }
+    fn sin(self) -> Self { self.chain(self.re.sin(), self.re.cos()) }
+    fn cos(self) -> Self { self.chain(self.re.cos(), -self.re.sin()) }
+    fn tan(self) -> Self { self.chain(self.re.tan(), 1.0 / (self.re.cos() * self.re.cos())) }
+    fn exp(self) -> Self { self.chain(self.re.exp(), self.re.exp()) }
+    fn log(self) -> Self { self.chain(self.re.ln(), 1.0 / self.re) }
+    fn asin(self) -> Self { self.chain(self.re.asin(), 1.0 / (1.0 - self.re * self.re).sqrt()) }
+    fn pow(self, exponent: Self) -> Self {
+        if exponent.eps == 0.0 {
+            let n = exponent.re;
+            return self.chain(self.re.powf(n), n * self.re.powf(n - 1.0));
+        }
+        (exponent * self.log()).exp()
+    }
+    fn re(self) -> Self { self }
+    fn im(self) -> Self { Dual::constant(0.0) }
+    fn conj(self) -> Self { self }
+    fn arg(self) -> Self { Dual::constant(self.re.arg()) }
+    fn abs(self) -> Self { self.chain(self.re.abs(), self.re.signum()) }
+}
+
+// `re + e1 ε1 + e2 ε2 + e12 ε1ε2` with ε1^2 = ε2^2 = 0. Seeding both ε1 and ε2 with
+// the same direction gives the first and second directional derivatives exactly.
+#[derive(Debug, Clone, Copy)]
+pub struct HyperDual {
+    re: f64,
+    e1: f64,
+    e2: f64,
+    e12: f64,
+}
+
+impl HyperDual {
+    fn chain(self, f: f64, df: f64, ddf: f64) -> HyperDual {
+        HyperDual {
+            re: f,
+            e1: df * self.e1,
+            e2: df * self.e2,
+            e12: df * self.e12 + ddf * self.e1 * self.e2,
+        }
+    }
+
+    fn is_constant(&self) -> bool {
+        self.e1 == 0.0 && self.e2 == 0.0 && self.e12 == 0.0
+    }
+}
+
+impl Add for HyperDual {
+    type Output = HyperDual;
+    fn add(self, rhs: HyperDual) -> HyperDual {
+        HyperDual { re: self.re + rhs.re, e1: self.e1 + rhs.e1, e2: self.e2 + rhs.e2, e12: self.e12 + rhs.e12 }
+    }
+}
+
+impl Sub for HyperDual {
+    type Output = HyperDual;
+    fn sub(self, rhs: HyperDual) -> HyperDual {
+        HyperDual { re: self.re - rhs.re, e1: self.e1 - rhs.e1, e2: self.e2 - rhs.e2, e12: self.e12 - rhs.e12 }
+    }
+}
+
+impl Mul for HyperDual {
+    type Output = HyperDual;
+    fn mul(self, rhs: HyperDual) -> HyperDual {
+        HyperDual {
+            re: self.re * rhs.re,
+            e1: self.re * rhs.e1 + self.e1 * rhs.re,
+            e2: self.re * rhs.e2 + self.e2 * rhs.re,
+            e12: self.re * rhs.e12 + self.e1 * rhs.e2 + self.e2 * rhs.e1 + self.e12 * rhs.re,
+        }
+    }
+}
+
+impl Div for HyperDual {
+    type Output = HyperDual;
+    fn div(self, rhs: HyperDual) -> HyperDual {
+        let re = rhs.re;
+        self * rhs.chain(1.0 / re, -1.0 / (re * re), 2.0 / (re * re * re))
+    }
+}
+
+impl Neg for HyperDual {
+    type Output = HyperDual;
+    fn neg(self) -> HyperDual { HyperDual { re: -self.re, e1: -self.e1, e2: -self.e2, e12: -self.e12 } }
+}
+
+impl Scalar for HyperDual {
+    fn constant(val: f64) -> Self { HyperDual { re: val, e1: 0.0, e2: 0.0, e12: 0.0 } }
+    fn imaginary() -> Option<Self> { None }
+    fn sin(self) -> Self { self.chain(self.re.sin(), self.re.cos(), -self.re.sin()) }
+    fn cos(self) -> Self { self.chain(self.re.cos(), -self.re.sin(), -self.re.cos()) }
+    fn tan(self) -> Self {
+        let sec2 = 1.0 / (self.re.cos() * self.re.cos());
+        self.chain(self.re.tan(), sec2, 2.0 * sec2 * self.re.tan())
+    }
+    fn exp(self) -> Self { self.chain(self.re.exp(), self.re.exp(), self.re.exp()) }
+    fn log(self) -> Self { self.chain(self.re.ln(), 1.0 / self.re, -1.0 / (self.re * self.re)) }
+    fn asin(self) -> Self {
+        let root = (1.0 - self.re * self.re).sqrt();
+        self.chain(self.re.asin(), 1.0 / root, self.re / (root * root * root))
+    }
+    fn pow(self, exponent: Self) -> Self {
+        if exponent.is_constant() {
+            let n = exponent.re;
+            return self.chain(self.re.powf(n), n * self.re.powf(n - 1.0), n * (n - 1.0) * self.re.powf(n - 2.0));
+        }
+        (exponent * self.log()).exp()
+    }
+    fn re(self) -> Self { self }
+    fn im(self) -> Self { HyperDual::constant(0.0) }
+    fn conj(self) -> Self { self }
+    fn arg(self) -> Self { HyperDual::constant(self.re.arg()) }
+    fn abs(self) -> Self { self.chain(self.re.abs(), self.re.signum(), 0.0) }
+}
+
+fn seed<T: Scalar>(vars: &[Rc<Node>], point: &[f64], direction: &[f64], make: impl Fn(f64, f64) -> T) -> Result<Env<T>, String> {
+    let mut env = Env::new();
+    for ((var, val), dir) in vars.iter().zip(point).zip(direction) {
+        match &**var {
+            Node::Var { name, .. } => {
+                env.insert(name.clone(), make(*val, *dir));
+            },
+            _ => return Err("expected a variable".to_string()),
+        }
+    }
+    Ok(env)
+}
+
+// Value and derivative of `node` at `point` along `direction`.
+pub fn derivative(node: &Rc<Node>, vars: &[Rc<Node>], point: &[f64], direction: &[f64]) -> Result<(f64, f64), String> {
+    let env = seed(vars, point, direction, |re, eps| Dual { re, eps })?;
+    let res = eval(node, &env)?;
+    Ok((res.re, res.eps))
+}
+
+// Value, first and second derivative of `node` at `point` along `direction`.
+pub fn second_derivative(node: &Rc<Node>, vars: &[Rc<Node>], point: &[f64], direction: &[f64]) -> Result<(f64, f64, f64), String> {
+    let env = seed(vars, point, direction, |re, dir| HyperDual { re, e1: dir, e2: dir, e12: 0.0 })?;
+    let res = eval(node, &env)?;
+    Ok((res.re, res.e1, res.e12))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    #[test]
+    fn directional_derivatives() {
+        let node = parse("var x, y; x ^ 2 * y + exp(y)");
+        let vars = ["x", "y"].map(|name| Node::var(name, None));
+        // Along (1, 1) at (1, 0): value 1, slope 2xy + x^2 + e^y = 2, curvature 2y + 4x + e^y = 5.
+        assert_eq!(derivative(&node, &vars, &[1.0, 0.0], &[1.0, 1.0]).unwrap(), (1.0, 2.0));
+        assert_eq!(second_derivative(&node, &vars, &[1.0, 0.0], &[1.0, 1.0]).unwrap(), (1.0, 2.0, 5.0));
+        let (_, slope) = derivative(&parse("var x; sin(x) ^ 2"), &vars[..1], &[0.5], &[1.0]).unwrap();
+        assert!((slope - 1f64.sin()).abs() < 1e-15);
+    }
+}
@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+use std::ops::{Add, Sub, Mul, Div, Neg};
+use std::rc::Rc;
+
+use crate::node::*;
+
+use BKind::*;
+use UKind::*;
+use Node::*;
+
+// Anything the evaluator can compute with: plain floats, complex numbers, or
+// number types that carry derivative information alongside the value.
+pub trait Scalar: Clone + Add<Output = Self> + Sub<Output = Self> + Mul<Output = Self> + Div<Output = Self> + Neg<Output = Self> {
+    fn constant(val: f64) -> Self;
+    // The imaginary unit, for the types that have one.
+    fn imaginary() -> Option<Self>;
+    fn sin(self) -> Self;
+    fn cos(self) -> Self;
+    fn tan(self) -> Self;
+    fn exp(self) -> Self;
+    fn log(self) -> Self;
+    fn asin(self) -> Self;
+    fn pow(self, exponent: Self) -> Self;
+    fn re(self) -> Self;
+    fn im(self) -> Self;
+    fn conj(self) -> Self;
+    fn arg(self) -> Self;
+    fn abs(self) -> Self;
+}
+
+impl Scalar for f64 {
+    fn constant(val: f64) -> Self { val }
+    fn imaginary() -> Option<Self> { None }
+    fn sin(self) -> Self { f64::sin(self) }
+    fn cos(self) -> Self { f64::cos(self) }
+    fn tan(self) -> Self { f64::tan(self) }
+    fn exp(self) -> Self { f64::exp(self) }
+    fn log(self) -> Self { f64::ln(self) }
+    fn asin(self) -> Self { f64::asin(self) }
+    fn pow(self, exponent: Self) -> Self { f64::powf(self, exponent) }
+    fn re(self) -> Self { self }
+    fn im(self) -> Self { 0.0 }
+    fn conj(self) -> Self { self }
+    fn arg(self) -> Self { f64::atan2(0.0, self) }
+    fn abs(self) -> Self { f64::abs(self) }
+}
+
+pub type Env<T> = HashMap<String, T>;
+
+// Evaluates `node` with the variables bound in `env`. A variable that is not bound
+// falls back to its defining expression, the same way `Node::dif` follows it.
+pub fn eval<T: Scalar>(node: &Rc<Node>, env: &Env<T>) -> Result<T, String> {
+    eval_shared(node, env, &mut HashMap::new())
+}
+
+fn eval_shared<T: Scalar>(node: &Rc<Node>, env: &Env<T>, memo: &mut HashMap<*const Node, T>) -> Result<T, String> {
+    if let Some(val) = memo.get(&Rc::as_ptr(node)) {
+        return Ok(val.clone());
+    }
+    let val = match &**node {
+        BinaryOperator { kind, lhs, rhs } => {
+            let lhs = eval_shared(lhs, env, memo)?;
+            let rhs = eval_shared(rhs, env, memo)?;
+            match kind {
+                Add => lhs + rhs,
+                Sub => lhs - rhs,
+                Mul => lhs * rhs,
+                Div => lhs / rhs,
+                Pow => lhs.pow(rhs),
+            }
+        },
+        UnaryOperator { kind, operand } => {
+            let operand = eval_shared(operand, env, memo)?;
+            match kind {
+                Neg => -operand,
+                Sin => operand.sin(),
+                Cos => operand.cos(),
+                Tan => operand.tan(),
+                Exp => operand.exp(),
+                Log => operand.log(),
+                Asin => operand.asin(),
+                Re => operand.re(),
+                Im => operand.im(),
+                Conj => operand.conj(),
+                Arg => operand.arg(),
+                Abs => operand.abs(),
+            }
+        },
+        Var { name, point, .. } => {
+            match (env.get(name), point) {
+                (Some(val), _) => val.clone(),
+                (None, Some(point)) => eval_shared(point, env, memo)?,
+                (None, None) => return Err(format!("{} has no value", name)),
+            }
+        },
+        // Bound under the name it prints as.
+        Derivative { .. } => match env.get(&node.to_string()) {
+            Some(val) => val.clone(),
+            None => return Err(format!("{} has no value", node)),
+        },
+        Num { val } => T::constant(*val as f64),
+        Const { kind } => match (kind.value(), T::imaginary()) {
+            (Some(val), _) => T::constant(val),
+            (None, Some(i)) => i,
+            (None, None) => return Err(format!("{} is not a real number", kind.name())),
+        },
+        List { .. } => return Err("a list has no value".to_string()),
+        Equation { .. } => return Err("an equation has no value".to_string()),
+        Unsolved { .. } => return Err(format!("{} has no closed form", node)),
+    };
+    memo.insert(Rc::as_ptr(node), val.clone());
+    Ok(val)
+}
@@ -0,0 +1,360 @@
+use std::rc::Rc;
+
+use crate::modular;
+use crate::node::*;
+use crate::poly::{negate_leading, number, Atom, Poly, MAX_POWER};
+use crate::rational::{self, overflow, Rational};
+
+use BKind::*;
+use Node::*;
+
+// Kronecker substitution is only tried when the univariate image stays small.
+const MAX_DENSE: usize = 121;
+
+// The leading coefficient of `p` as a polynomial in `x`.
+pub fn leading_in(p: &Poly, x: &Atom) -> Poly {
+    p.powers(x).remove(&p.degree_in(x)).unwrap_or_else(|| Poly::constant(Rational::int(0)))
+}
+
+// The remainder of `a` by `b` as polynomials in `x`, after scaling `a` by
+// powers of the leading coefficient of `b` so that the division never needs
+// to divide by it. Only the result up to a constant factor is kept.
+fn prem(a: &Poly, b: &Poly, x: &Atom) -> Result<Poly, String> {
+    let db = b.degree_in(x);
+    let lb = leading_in(b, x);
+    let mut rem = a.clone();
+    while !rem.is_zero() && rem.degree_in(x) >= db {
+        let shift = b.power_of(x, rem.degree_in(x) - db);
+        rem = rem.mul(&lb)?.sub(&leading_in(&rem, x).mul(&shift)?.mul(b)?)?.primitive()?;
+    }
+    Ok(rem)
+}
+
+// The gcd of the coefficients of `p` as a polynomial in `x`.
+fn content_in(p: &Poly, x: &Atom) -> Result<Poly, String> {
+    p.powers(x).values().try_fold(Poly::constant(Rational::int(0)), |acc, c| gcd(&acc, c))
+}
+
+// `p / q` where `q` is known to divide `p`.
+fn quotient(p: &Poly, q: &Poly) -> Result<Poly, String> {
+    Ok(p.div_exact(q)?.expect("a gcd or content divides its polynomials"))
+}
+
+// The greatest common divisor over the rationals, with integer coprime
+// coefficients and a positive leading coefficient. Multivariate polynomials
+// are treated as polynomials in their first atom whose coefficients are
+// polynomials in the rest.
+pub fn gcd(a: &Poly, b: &Poly) -> Result<Poly, String> {
+    if a.is_zero() || b.is_zero() {
+        return a.add(b)?.primitive();
+    }
+    let Some(x) = a.atoms().into_iter().chain(b.atoms()).min() else { return Ok(Poly::one()) };
+    if a.degree_in(&x) == 0 {
+        return gcd(a, &content_in(b, &x)?);
+    }
+    if b.degree_in(&x) == 0 {
+        return gcd(&content_in(a, &x)?, b);
+    }
+    if a.atoms().len() == 1 && b.atoms().len() == 1 {
+        let atoms = [x];
+        let base = a.degree_in(&atoms[0]).max(b.degree_in(&atoms[0])) as usize + 1;
+        let (a, b) = (a.primitive()?, b.primitive()?);
+        if let Some(g) = modular::gcd(&a.to_dense(&atoms, base), &b.to_dense(&atoms, base)) {
+            return Ok(a.with_dense(&g, &atoms, base));
+        }
+        return prs(&a, &b, &atoms[0]);
+    }
+    prs(a, b, &x)
+}
+
+// The gcd of the contents times the last nonzero remainder of a primitive
+// pseudo-remainder sequence.
+fn prs(a: &Poly, b: &Poly, x: &Atom) -> Result<Poly, String> {
+    let (ca, cb) = (content_in(a, x)?, content_in(b, x)?);
+    let mut p = quotient(a, &ca)?;
+    let mut q = quotient(b, &cb)?;
+    if p.degree_in(x) < q.degree_in(x) {
+        (p, q) = (q, p);
+    }
+    loop {
+        let rem = prem(&p, &q, x)?;
+        if rem.is_zero() {
+            break;
+        }
+        if rem.degree_in(x) == 0 {
+            q = Poly::one();
+            break;
+        }
+        p = q;
+        q = quotient(&rem, &content_in(&rem, x)?)?;
+    }
+    gcd(&ca, &cb)?.mul(&q)?.primitive()
+}
+
+// Yun's algorithm in the first atom, after taking out the content, which is
+// handled the same way in the remaining atoms. `f` must be primitive, and the
+// factors come out primitive, pairwise coprime and square-free.
+fn square_free(f: &Poly) -> Result<Vec<(Poly, u32)>, String> {
+    let Some(x) = f.atoms().into_iter().min() else { return Ok(Vec::new()) };
+    let content = content_in(f, &x)?;
+    let mut res = square_free(&content)?;
+    let f = quotient(f, &content)?;
+    let df = f.derivative(&x)?;
+    let a = gcd(&f, &df)?;
+    let mut b = quotient(&f, &a)?;
+    let mut d = quotient(&df, &a)?.sub(&b.derivative(&x)?)?;
+    let mut i = 1;
+    while b.as_constant().is_none() {
+        let a = gcd(&b, &d)?;
+        let c = quotient(&d, &a)?;
+        b = quotient(&b, &a)?;
+        d = c.sub(&b.derivative(&x)?)?;
+        if a.as_constant().is_none() {
+            res.push((a, i));
+        }
+        i += 1;
+    }
+    Ok(res)
+}
+
+fn dense_mul(a: &[i128], b: &[i128]) -> Result<Vec<i128>, String> {
+    let mut res = vec![0i128; a.len() + b.len() - 1];
+    for (i, x) in a.iter().enumerate() {
+        for (j, y) in b.iter().enumerate() {
+            res[i + j] = x.checked_mul(*y).and_then(|xy| res[i + j].checked_add(xy)).ok_or_else(overflow)?;
+        }
+    }
+    Ok(res)
+}
+
+// The irreducible factors of a primitive, square-free `f`. Several atoms are
+// first turned into one by a Kronecker substitution; every factor of `f` maps
+// to a product of factors of the image, so subsets of those are tried as
+// divisors, smallest first. Polynomials too large for that are left whole.
+fn irreducible(f: &Poly) -> Result<Vec<Poly>, String> {
+    let atoms = f.atoms();
+    let base = atoms.iter().map(|atom| f.degree_in(atom)).max().unwrap_or(0) as usize + 1;
+    if atoms.is_empty() || base.checked_pow(atoms.len() as u32).is_none_or(|size| size > MAX_DENSE) {
+        return Ok(vec![f.clone()]);
+    }
+    if atoms.len() == 1 {
+        return Ok(modular::factor(&f.to_dense(&atoms, base)).iter().map(|g| f.with_dense(g, &atoms, base)).collect());
+    }
+    // The image need not be square-free, so its factors are listed with their
+    // multiplicities.
+    let size = base.pow(atoms.len() as u32);
+    let image = f.with_dense(&f.to_dense(&atoms, base), &atoms[..1], size);
+    let mut factors = Vec::new();
+    for (g, m) in square_free(&image.primitive()?)? {
+        for h in modular::factor(&g.to_dense(&atoms[..1], size)) {
+            factors.extend((0..m).map(|_| h.clone()));
+        }
+    }
+    let mut f = f.clone();
+    let mut res = Vec::new();
+    let mut size = 1;
+    'grow: while 2 * size <= factors.len() {
+        for subset in modular::combinations(factors.len(), size) {
+            let image = subset.iter().try_fold(vec![1], |acc, i| dense_mul(&acc, &factors[*i]))?;
+            let candidate = f.with_dense(&image, &atoms, base).primitive()?;
+            if let Some(quotient) = f.div_exact(&candidate)? {
+                res.push(candidate);
+                f = quotient;
+                factors = factors.into_iter().enumerate().filter(|(i, _)| !subset.contains(i)).map(|(_, g)| g).collect();
+                continue 'grow;
+            }
+        }
+        size += 1;
+    }
+    res.push(f.primitive()?);
+    Ok(res)
+}
+
+// `f` as a constant times powers of primitive polynomials.
+pub fn factor_list(f: &Poly, complete: bool) -> Result<(Rational, Vec<(Poly, u32)>), String> {
+    let factors = square_free(&f.primitive()?)?;
+    if !complete {
+        return Ok((f.content()?, factors));
+    }
+    let mut res = Vec::new();
+    for (g, m) in factors {
+        for h in irreducible(&g)? {
+            let name = h.to_node()?.to_string();
+            res.push(((h.total_degree(), name.len(), name), h, m));
+        }
+    }
+    res.sort_by(|(a, ..), (b, ..)| a.cmp(b));
+    Ok((f.content()?, res.into_iter().map(|(_, h, m)| (h, m)).collect()))
+}
+
+fn product(factors: &[(Poly, u32)]) -> Result<Vec<Rc<Node>>, String> {
+    factors
+        .iter()
+        .map(|(g, m)| Ok(if *m == 1 { g.to_node()? } else { Node::binary(Pow, g.to_node()?, Node::num(*m as f32)) }))
+        .collect()
+}
+
+// The quotient `p / q` of polynomials in lowest terms, as polynomials with
+// integer coefficients, the denominator primitive with a positive lead.
+fn reduce(p: &Poly, q: &Poly) -> Result<(Poly, Poly), String> {
+    if p.is_zero() {
+        return Ok((p.clone(), Poly::one()));
+    }
+    let g = gcd(p, q)?;
+    let (p, q) = (quotient(p, &g)?, quotient(q, &g)?);
+    let c = q.content()?;
+    Ok((p.mul(&Poly::constant(c.recip()?))?, q.primitive()?))
+}
+
+// `node` as a quotient of polynomials in lowest terms. Anything that is not a
+// sum, product or integer power counts as an atom, with its arguments
+// cancelled in turn.
+pub fn fraction(node: &Rc<Node>) -> Result<(Poly, Poly), String> {
+    match &**node {
+        Var { point: Some(point), .. } => fraction(point),
+        Num { .. } | Var { .. } | Derivative { .. } => Ok((Poly::from_node(node)?, Poly::one())),
+        BinaryOperator { kind: kind @ (Add | Sub | Mul | Div), lhs, rhs } => {
+            let ((a, b), (c, d)) = (fraction(lhs)?, fraction(rhs)?);
+            match kind {
+                Add => reduce(&a.mul(&d)?.add(&c.mul(&b)?)?, &b.mul(&d)?),
+                Sub => reduce(&a.mul(&d)?.sub(&c.mul(&b)?)?, &b.mul(&d)?),
+                Mul => reduce(&a.mul(&c)?, &b.mul(&d)?),
+                _ if c.is_zero() => Ok((Poly::atom(node.clone(), 1), Poly::one())),
+                _ => reduce(&a.mul(&d)?, &b.mul(&c)?),
+            }
+        },
+        BinaryOperator { kind: Pow, lhs, rhs } => match Poly::from_node(rhs)?.as_constant() {
+            Some(n) if n.is_integer() && n.num.abs() <= MAX_POWER as i128 => {
+                let (a, b) = fraction(lhs)?;
+                let n = n.num as i32;
+                if n >= 0 {
+                    Ok((a.pow(n as u32)?, b.pow(n as u32)?))
+                } else if a.is_zero() {
+                    Ok((Poly::atom(node.clone(), 1), Poly::one()))
+                } else {
+                    reduce(&b.pow(n.unsigned_abs())?, &a.pow(n.unsigned_abs())?)
+                }
+            },
+            _ => Ok((Poly::atom(Node::binary(Pow, cancel(lhs), cancel(rhs)), 1), Poly::one())),
+        },
+        UnaryOperator { kind: UKind::Neg, operand } => {
+            let (a, b) = fraction(operand)?;
+            Ok((a.neg(), b))
+        },
+        UnaryOperator { kind, operand } => Ok((Poly::atom(Node::unary(*kind, cancel(operand)), 1), Poly::one())),
+        Const { .. } | List { .. } | Equation { .. } | Unsolved { .. } => Ok((Poly::atom(node.clone(), 1), Poly::one())),
+    }
+}
+
+fn map(node: &Rc<Node>, f: fn(&Rc<Node>) -> Result<Rc<Node>, String>) -> Result<Rc<Node>, String> {
+    match &**node {
+        Equation { lhs, rhs } => Ok(Node::equation(map(lhs, f)?, map(rhs, f)?)),
+        List { items } => Ok(Node::list(items.iter().map(|item| map(item, f)).collect::<Result<_, _>>()?)),
+        _ => f(node),
+    }
+}
+
+fn cancelled(node: &Rc<Node>) -> Result<Rc<Node>, String> {
+    let (p, q) = fraction(node)?;
+    if q.as_constant().is_some() {
+        return p.to_node();
+    }
+    let c = p.content()?;
+    let p = p.primitive()?.mul(&Poly::constant(Rational::int(c.num)))?;
+    let q = q.mul(&Poly::constant(Rational::int(c.den)))?;
+    Ok(Node::binary(Div, p.to_node()?, q.to_node()?))
+}
+
+// Brings a rational expression over a common denominator and cancels the gcd
+// of numerator and denominator, leaving both expanded. Expressions whose
+// coefficients outgrow an `i128` are left as they are.
+pub fn cancel(node: &Rc<Node>) -> Rc<Node> {
+    map(node, |node| Ok(cancelled(node).unwrap_or_else(|_| node.clone()))).unwrap_or_else(|_| node.clone())
+}
+
+// Writes a cancelled quotient as a constant times powers of its factors.
+fn factored(node: &Rc<Node>, complete: bool) -> Result<Rc<Node>, String> {
+    let (p, q) = fraction(node)?;
+    if p.is_zero() {
+        return Ok(Node::num(0.0));
+    }
+    let (c, num) = factor_list(&p, complete)?;
+    let (d, den) = factor_list(&q, complete)?;
+    let c = c.checked_div(d)?;
+    let mut num = product(&num)?;
+    let mut den = product(&den)?;
+    if c.num.abs() != 1 || num.is_empty() {
+        num.insert(0, number(Rational::int(c.num.abs()))?);
+    }
+    if c.den != 1 {
+        den.insert(0, number(Rational::int(c.den))?);
+    }
+    let num = num.into_iter().reduce(|acc, factor| Node::binary(Mul, acc, factor)).unwrap();
+    let res = match den.into_iter().reduce(|acc, factor| Node::binary(Mul, acc, factor)) {
+        Some(den) => Node::binary(Div, num, den),
+        None => num,
+    };
+    Ok(if c.num < 0 { negate_leading(&res) } else { res })
+}
+
+// Factors numerator and denominator into irreducible polynomials over the
+// rationals.
+pub fn factor(node: &Rc<Node>) -> Result<Rc<Node>, String> {
+    map(node, |node| factored(node, true))
+}
+
+// Factors numerator and denominator into powers of square-free, pairwise
+// coprime polynomials.
+pub fn sqf(node: &Rc<Node>) -> Result<Rc<Node>, String> {
+    map(node, |node| factored(node, false))
+}
+
+fn polynomial(node: &Rc<Node>) -> Result<Poly, String> {
+    let (p, q) = fraction(node)?;
+    match q.as_constant() {
+        Some(c) => p.mul(&Poly::constant(c.recip()?)),
+        None => Err(format!("{} is not a polynomial", node)),
+    }
+}
+
+// The gcd of two polynomials, including the gcd of their contents.
+pub fn gcd_of(p: &Rc<Node>, q: &Rc<Node>) -> Result<Rc<Node>, String> {
+    let (p, q) = (polynomial(p)?, polynomial(q)?);
+    let (c, d) = (p.content()?.abs(), q.content()?.abs());
+    let content = match (p.is_zero(), q.is_zero()) {
+        (true, _) => d,
+        (_, true) => c,
+        _ => {
+            let den = (c.den / rational::gcd(c.den, d.den)).checked_mul(d.den).ok_or_else(overflow)?;
+            Rational::new(rational::gcd(c.num, d.num), den)?
+        },
+    };
+    gcd(&p, &q)?.mul(&Poly::constant(content))?.to_node()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    fn factored(src: &str) -> String {
+        factor(&parse(&format!("var x, y; {}", src))).unwrap().to_string()
+    }
+
+    #[test]
+    fn factors_over_the_integers() {
+        // No rational roots, yet a product of two quadratics.
+        assert_eq!(factored("x ^ 4 + 4"), "(x ^ 2 + 2 * x + 2) * (x ^ 2 - 2 * x + 2)");
+        assert_eq!(factored("x ^ 6 - 1"), "(x + 1) * (x - 1) * (x ^ 2 + x + 1) * (x ^ 2 - x + 1)");
+        assert_eq!(factored("2 * x ^ 2 - 8"), "2 * (x + 2) * (x - 2)");
+        assert_eq!(factored("x ^ 2 + 1"), "x ^ 2 + 1");
+    }
+
+    #[test]
+    fn gcds_and_cancellation() {
+        let gcd = |p: &str, q: &str| gcd_of(&parse(&format!("var x; {}", p)), &parse(&format!("var x; {}", q))).unwrap().to_string();
+        assert_eq!(gcd("x ^ 4 - 1", "x ^ 6 - 1"), "x ^ 2 - 1");
+        assert_eq!(gcd("2 * x ^ 2 + 4 * x + 2", "4 * x ^ 2 - 4"), "2 * x + 2");
+        assert_eq!(cancel(&parse("var x; (x ^ 2 - 1) / (x ^ 2 + 2 * x + 1)")).to_string(), "(x - 1) / (x + 1)");
+    }
+}
@@ -0,0 +1,56 @@
+use std::rc::Rc;
+
+use crate::factor::cancel;
+use crate::node::*;
+use crate::simplify::simplify;
+
+use BKind::*;
+use UKind::*;
+use Node::*;
+
+// Each order differentiates the last, which grows quickly, so orders are capped.
+pub const MAX_ORDER: usize = 8;
+
+// The `n`th derivative of `y` with respect to `x` along the curve `F(x, y) = 0`.
+// The first is `-F_x / F_y`; each further one differentiates the previous
+// through `y` as well, `d/dx g(x, y) = g_x + g_y * y'`.
+pub fn implicit_dif(relation: &Rc<Node>, y: &Rc<Node>, x: &Rc<Node>, n: usize) -> Result<Rc<Node>, String> {
+    for var in [y, x] {
+        if !matches!(**var, Var { point: None, .. }) {
+            return Err(format!("{} is not a free variable", var));
+        }
+    }
+    if n == 0 {
+        return Err("the order of a derivative is at least 1".to_string());
+    }
+    let f = match &**relation {
+        Equation { lhs, rhs } => Node::binary(Sub, lhs.clone(), rhs.clone()),
+        _ => relation.clone(),
+    };
+    let f_y = cancel(&simplify(&Node::dif(&f, y)));
+    if matches!(*f_y, Num { val } if val == 0.0) {
+        return Err(format!("{} does not depend on {}", f, y));
+    }
+    let first = cancel(&simplify(&Node::unary(Neg, Node::binary(Div, Node::dif(&f, x), f_y))));
+    let mut res = first.clone();
+    for _ in 1..n {
+        let total = Node::binary(Add, Node::dif(&res, x), Node::binary(Mul, Node::dif(&res, y), first.clone()));
+        res = cancel(&simplify(&total));
+    }
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    #[test]
+    fn circle() {
+        let circle = parse("var x, y; x ^ 2 + y ^ 2 == 1");
+        let (x, y) = (Node::var("x", None), Node::var("y", None));
+        assert_eq!(implicit_dif(&circle, &y, &x, 1).unwrap().to_string(), "-x / y");
+        // On the circle `-(x^2 + y^2) / y^3` is `-1 / y^3`.
+        assert_eq!(implicit_dif(&circle, &y, &x, 2).unwrap().to_string(), "(-(x ^ 2) - y ^ 2) / y ^ 3");
+    }
+}
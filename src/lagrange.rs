@@ -0,0 +1,45 @@
+use std::rc::Rc;
+
+use crate::factor::cancel;
+use crate::matrix::items;
+use crate::node::*;
+use crate::simplify::simplify;
+
+use BKind::*;
+use Node::*;
+
+// The equations of motion `d/dt (dL/dq') - dL/dq == 0` of the Lagrangian `l`,
+// one per generalised coordinate. Each coordinate must be declared as a
+// function of `t`, as in `var q(t)`, so that `q' = dif(q, t)` is a symbol of its
+// own that `l` can be differentiated by.
+pub fn euler_lagrange(l: &Rc<Node>, coords: &Rc<Node>, t: &Rc<Node>) -> Result<Rc<Node>, String> {
+    let Var { name: time, point: None, .. } = &**t else {
+        return Err(format!("{} is not a free variable", t));
+    };
+    let coords = items(coords).unwrap_or_else(|| vec![coords.clone()]);
+    let mut equations = Vec::new();
+    for q in &coords {
+        match &**q {
+            Var { deps, .. } if deps.contains(time) => {},
+            _ => return Err(format!("{} is not declared as a function of {}, as in 'var {}({})'", q, time, q, time)),
+        }
+        let velocity = Node::dif(q, t);
+        let momentum = Node::dif(l, &velocity);
+        let lhs = Node::binary(Sub, Node::dif(&momentum, t), Node::dif(l, q));
+        equations.push(Node::equation(cancel(&simplify(&lhs)), Node::num(0.0)));
+    }
+    Ok(Node::list(equations))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    #[test]
+    fn pendulum() {
+        let lagrangian = parse("var t, g, l, m, q(t); m * l ^ 2 * dif(q, t) ^ 2 / 2 + m * g * l * cos(q)");
+        let equations = euler_lagrange(&lagrangian, &parse("var q(t); [q]"), &Node::var("t", None)).unwrap();
+        assert_eq!(equations.to_string(), "[g * l * m * sin(q) + l ^ 2 * m * dif(dif(q, t), t) == 0]");
+    }
+}
@@ -4,7 +4,8 @@ use Token::*;
 pub enum Token {
     Reserved(String),
     Ident(String),
-    Num(u32),
+    Num(f32),
+    Str(String),
 }
 
 pub struct Lexer {
@@ -12,7 +13,7 @@ pub struct Lexer {
     pos: usize,
 }
 
-const KEYWORDS: [&str; 9] = ["var", "op", "sin", "cos", "tan", "exp", "log", "dif", "print"];
+const KEYWORDS: [&str; 18] = ["var", "let", "op", "sin", "cos", "tan", "exp", "log", "dif", "print", "gradat", "evalat", "difat", "difat2", "codegen", "table", "plot", "solve_numeric"];
 const PUNCTS: [char; 16] = ['=', '+', '-', '*', '/', '^', '.', ',', ':', ';', '(', ')', '{', '}', '[', ']'];
 
 impl Lexer {
@@ -23,12 +24,17 @@ impl Lexer {
         }
     }
 
+    // The character `offset` places ahead, or '\0' past the end of the code.
+    fn peek(&self, offset: usize) -> char {
+        self.chs.get(self.pos + offset).copied().unwrap_or('\0')
+    }
+
     pub fn tokenize(&mut self) -> Vec<Token> {
         let mut token_list = Vec::new();
         while self.pos < self.chs.len() {
             let mut c = self.chs[self.pos];
-            if c == '/' && self.chs[self.pos+1] == '/' {
-                while self.chs[self.pos] != '\n' {
+            if c == '/' && self.peek(1) == '/' {
+                while self.pos < self.chs.len() && self.chs[self.pos] != '\n' {
                     self.pos += 1;
                 }
                 continue;
@@ -37,7 +43,28 @@ impl Lexer {
                 self.pos += 1;
                 continue;
             }
-            if PUNCTS.to_vec().iter().find(|&&x| x == c) != None {
+            if c == '"' {
+                let mut text = "".to_string();
+                self.pos += 1;
+                while self.pos < self.chs.len() && self.chs[self.pos] != '"' {
+                    text.push(self.chs[self.pos]);
+                    self.pos += 1;
+                }
+                self.pos += 1;
+                token_list.push(Str(text));
+                continue;
+            }
+            if c == '=' && self.peek(1) == '=' {
+                token_list.push(Reserved("==".to_string()));
+                self.pos += 2;
+                continue;
+            }
+            if c == '.' && self.peek(1) == '*' {
+                token_list.push(Reserved(".*".to_string()));
+                self.pos += 2;
+                continue;
+            }
+            if PUNCTS.contains(&c) {
                 token_list.push(Reserved(c.to_string()));
                 self.pos += 1;
                 continue;
@@ -47,23 +74,35 @@ impl Lexer {
                 while c.is_ascii_alphanumeric() || c == '_' {
                     name.push(c);
                     self.pos += 1;
-                    c = self.chs[self.pos];
+                    c = self.peek(0);
                 }
-                if KEYWORDS.to_vec().iter().find(|&&x| x == name) != None {
+                if KEYWORDS.contains(&name.as_str()) {
                     token_list.push(Reserved(name));
                 } else {
                     token_list.push(Ident(name));
                 }
                 continue;
             }
-            if c.is_digit(10) {
-                let mut val = 0;
-                while c.is_digit(10) {
-                    val = 10 * val + c.to_digit(10).unwrap();
+            if c.is_ascii_digit() {
+                let mut text = "".to_string();
+                while c.is_ascii_digit() {
+                    text.push(c);
                     self.pos += 1;
-                    c = self.chs[self.pos];
+                    c = self.peek(0);
                 }
-                token_list.push(Num(val));
+                // A '.' only starts a fraction when a digit follows, so ranges
+                // such as `0..10` still lex as two numbers.
+                if c == '.' && self.peek(1).is_ascii_digit() {
+                    text.push(c);
+                    self.pos += 1;
+                    c = self.peek(0);
+                    while c.is_ascii_digit() {
+                        text.push(c);
+                        self.pos += 1;
+                        c = self.peek(0);
+                    }
+                }
+                token_list.push(Num(text.parse().unwrap()));
                 continue;
             }
             break;
@@ -78,6 +117,9 @@ impl Lexer {
         //         },
         //         Num(val) => {
         //             print!("{} ", val);
+        //         },
+        //         Str(text) => {
+        //             print!("\"{}\" ", text);
         //         }
         //     }
         // }
@@ -0,0 +1,541 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::eval::*;
+use crate::factor::cancel;
+use crate::node::*;
+use crate::poly::var_name;
+use crate::simplify::{has_indeterminate, simplify};
+
+use BKind::*;
+use UKind::*;
+use Node::*;
+
+// Rewrites of an indeterminate form, such as a step of L'Hôpital's rule, that
+// may follow each other before giving up.
+const MAX_REWRITES: usize = 8;
+// Derivatives tried when comparing the orders of numerator and denominator.
+const MAX_ORDER: usize = 8;
+// Distances from the limit point at which the sign of an expression is read,
+// relative to the size of the point. The closest defined one wins.
+const STEPS: [f64; 3] = [1e-4, 1e-6, 1e-8];
+// Distance from the point, relative to its size, at which continuity is checked.
+const CONTINUITY_STEP: f64 = 1e-12;
+// Values given to other variables to check that a substituted expression is
+// defined.
+const GENERIC: f64 = 0.7390851332151607;
+
+#[derive(Debug, Clone)]
+enum Limit {
+    Finite(Rc<Node>),
+    // Positive or negative infinity, by the sign of the value.
+    Inf(f64),
+}
+
+use Limit::*;
+
+fn value(node: &Rc<Node>) -> Option<f64> {
+    eval::<f64>(node, &Env::new()).ok().filter(|val| val.is_finite())
+}
+
+// The value with every variable replaced by a fixed, unremarkable number.
+fn generic_value(node: &Rc<Node>) -> Option<f64> {
+    generic_value_at(node, "", 0.0)
+}
+
+// The same with `name` bound to `x` instead.
+fn generic_value_at(node: &Rc<Node>, name: &str, x: f64) -> Option<f64> {
+    let others = Node::free_vars(node).into_iter().filter(|other| other != name);
+    let mut env = others.enumerate().map(|(i, name)| (name, GENERIC + i as f64)).collect::<Env<f64>>();
+    env.insert(name.to_string(), x);
+    eval::<f64>(node, &env).ok().filter(|val| val.is_finite())
+}
+
+fn is_zero(node: &Rc<Node>) -> bool {
+    value(node).is_some_and(|val| val.abs() < 1e-12)
+}
+
+// Whether `node` stays within fixed bounds whatever its variables are, as
+// `sin` and `cos` do.
+fn bounded(node: &Rc<Node>) -> bool {
+    match &**node {
+        UnaryOperator { kind: Sin | Cos, .. } => true,
+        UnaryOperator { kind: Neg | Abs, operand } => bounded(operand),
+        BinaryOperator { kind: Add | Sub | Mul, lhs, rhs } => bounded(lhs) && bounded(rhs),
+        Var { point: Some(point), .. } => bounded(point),
+        _ => value(node).is_some(),
+    }
+}
+
+// `1 / node`, without stacking reciprocals.
+fn recip(node: &Rc<Node>) -> Rc<Node> {
+    match &**node {
+        BinaryOperator { kind: Div, lhs, rhs } if matches!(**lhs, Num { val } if val == 1.0) => rhs.clone(),
+        BinaryOperator { kind: Div, lhs, rhs } => Node::binary(Div, rhs.clone(), lhs.clone()),
+        BinaryOperator { kind: Pow, lhs, rhs } => simplify(&Node::binary(Pow, lhs.clone(), Node::unary(Neg, rhs.clone()))),
+        UnaryOperator { kind: Exp, operand } => Node::unary(Exp, simplify(&Node::unary(Neg, operand.clone()))),
+        _ => Node::binary(Div, Node::num(1.0), node.clone()),
+    }
+}
+
+fn describe(limit: &Limit) -> String {
+    match limit {
+        Finite(node) => simplify(node).to_string(),
+        Inf(sign) if *sign > 0.0 => "inf".to_string(),
+        Inf(_) => "-inf".to_string(),
+    }
+}
+
+// How the variable approaches the limit point: from one side of a finite point,
+// or towards positive or negative infinity.
+struct Approach {
+    var: Rc<Node>,
+    name: String,
+    at: Option<Rc<Node>>,
+    at_val: f64,
+    side: f64,
+}
+
+impl Approach {
+    // The value of `node` close enough to the point, which may overflow.
+    fn near(&self, node: &Rc<Node>, step: f64) -> Option<f64> {
+        let x = match self.at {
+            Some(_) => self.at_val + self.side * step * self.at_val.abs().max(1.0),
+            None => self.side / step,
+        };
+        eval::<f64>(node, &Env::from([(self.name.clone(), x)])).ok().filter(|val| !val.is_nan())
+    }
+
+    // The sign of `node` close enough to the point.
+    fn sign_near(&self, node: &Rc<Node>) -> Result<f64, String> {
+        match STEPS.iter().rev().filter_map(|step| self.near(node, *step)).find(|val| *val != 0.0) {
+            Some(val) => Ok(val.signum()),
+            None => Err(format!("cannot tell the sign of {} near the limit point", node)),
+        }
+    }
+
+    fn sign_of(&self, node: &Rc<Node>) -> Result<f64, String> {
+        match value(node) {
+            Some(val) if val != 0.0 => Ok(val.signum()),
+            _ => Err(format!("cannot tell the sign of {}", node)),
+        }
+    }
+
+    fn finite(&self, node: Rc<Node>) -> Limit {
+        Finite(simplify(&node))
+    }
+
+    fn lim(&self, node: &Rc<Node>, rewrites: usize) -> Result<Limit, String> {
+        match &**node {
+            Var { name, .. } if *name == self.name => Ok(match &self.at {
+                Some(at) => Finite(at.clone()),
+                None => Inf(self.side),
+            }),
+            Var { point: Some(point), .. } => self.lim(point, rewrites),
+            Var { point: None, .. } | Num { .. } | Derivative { .. } => Ok(Finite(node.clone())),
+            Const { kind: CKind::Inf } => Ok(Inf(1.0)),
+            Const { .. } => Ok(Finite(node.clone())),
+            List { .. } | Equation { .. } | Unsolved { .. } => Err(format!("{} is not a scalar expression", node)),
+            BinaryOperator { kind: kind @ (Add | Sub), lhs, rhs } => {
+                let negate = |limit: Limit| match limit {
+                    Inf(sign) if *kind == Sub => Inf(-sign),
+                    limit => limit,
+                };
+                match (self.lim(lhs, rewrites)?, negate(self.lim(rhs, rewrites)?)) {
+                    (Finite(a), Finite(b)) => Ok(self.finite(Node::binary(*kind, a, b))),
+                    (Finite(_), Inf(sign)) | (Inf(sign), Finite(_)) => Ok(Inf(sign)),
+                    (Inf(s), Inf(t)) if s == t => Ok(Inf(s)),
+                    _ => self.difference(node, lhs, rhs, *kind, rewrites),
+                }
+            },
+            BinaryOperator { kind: Mul, lhs, rhs } => {
+                // A bounded factor times one that tends to zero tends to zero,
+                // even when the bounded one oscillates.
+                let (a, b) = (self.lim(lhs, rewrites), self.lim(rhs, rewrites));
+                let to_zero = |limit: &Result<Limit, String>| matches!(limit, Ok(Finite(a)) if is_zero(a));
+                if to_zero(&a) && bounded(rhs) || to_zero(&b) && bounded(lhs) {
+                    return Ok(Finite(Node::num(0.0)));
+                }
+                match (a?, b?) {
+                    (Finite(a), Finite(b)) => Ok(self.finite(Node::binary(Mul, a, b))),
+                    (Finite(a), Inf(_)) if is_zero(&a) => self.product(rhs, lhs, rewrites),
+                    (Inf(_), Finite(b)) if is_zero(&b) => self.product(lhs, rhs, rewrites),
+                    _ => Ok(Inf(self.sign_near(node)?)),
+                }
+            },
+            BinaryOperator { kind: Div, lhs, rhs } => {
+                let (a, b) = (self.lim(lhs, rewrites), self.lim(rhs, rewrites)?);
+                if matches!(b, Inf(_)) && bounded(lhs) {
+                    return Ok(Finite(Node::num(0.0)));
+                }
+                match (a?, b) {
+                    (Finite(a), Finite(b)) if !is_zero(&b) => Ok(self.finite(Node::binary(Div, a, b))),
+                    (Finite(a), Finite(_)) if is_zero(&a) => self.quotient(lhs, rhs, rewrites),
+                    (Finite(_), Inf(_)) => Ok(Finite(Node::num(0.0))),
+                    (Inf(_), Inf(_)) => self.quotient(lhs, rhs, rewrites),
+                    _ => Ok(Inf(self.sign_near(node)?)),
+                }
+            },
+            BinaryOperator { kind: Pow, lhs, rhs } => self.power(node, lhs, rhs, rewrites),
+            UnaryOperator { kind: Tan, operand } => {
+                let quotient = Node::binary(Div, Node::unary(Sin, operand.clone()), Node::unary(Cos, operand.clone()));
+                self.lim(&quotient, rewrites)
+            },
+            UnaryOperator { kind, operand } => match (kind, self.lim(operand, rewrites)?) {
+                (Neg, Finite(a)) => Ok(self.finite(Node::unary(Neg, a))),
+                (Neg, Inf(sign)) => Ok(Inf(-sign)),
+                (Exp, Inf(sign)) if sign > 0.0 => Ok(Inf(1.0)),
+                (Exp, Inf(_)) => Ok(Finite(Node::num(0.0))),
+                (Log, Finite(a)) if is_zero(&a) => Ok(Inf(-1.0)),
+                (Log, Inf(sign)) if sign > 0.0 => Ok(Inf(1.0)),
+                (Log, Inf(_)) => Err(format!("{} is undefined towards -inf", node)),
+                (Abs, Inf(_)) => Ok(Inf(1.0)),
+                (_, Finite(a)) => Ok(self.finite(Node::unary(*kind, a))),
+                (_, Inf(_)) => Err(format!("cannot determine the limit: {} oscillates", node)),
+            },
+        }
+    }
+
+    // `node` with each `abs(u)` replaced by `u` or `-u`, by the sign `u` keeps
+    // close to the point, since derivatives of `abs` bring it back.
+    fn unsigned(&self, node: &Rc<Node>) -> Rc<Node> {
+        fn visit(approach: &Approach, node: &Rc<Node>, memo: &mut HashMap<*const Node, Rc<Node>>) -> Rc<Node> {
+            if let Some(res) = memo.get(&Rc::as_ptr(node)) {
+                return res.clone();
+            }
+            let res = match &**node {
+                UnaryOperator { kind: Abs, operand } => {
+                    let operand = visit(approach, operand, memo);
+                    match approach.sign_near(&operand) {
+                        Ok(sign) if sign > 0.0 => operand,
+                        Ok(_) => Node::unary(Neg, operand),
+                        Err(_) => Node::unary(Abs, operand),
+                    }
+                },
+                BinaryOperator { kind, lhs, rhs } => Node::binary(*kind, visit(approach, lhs, memo), visit(approach, rhs, memo)),
+                UnaryOperator { kind, operand } => Node::unary(*kind, visit(approach, operand, memo)),
+                Var { .. } | Num { .. } | Const { .. } | List { .. } | Derivative { .. } | Equation { .. } | Unsolved { .. } => node.clone(),
+            };
+            memo.insert(Rc::as_ptr(node), res.clone());
+            res
+        }
+        visit(self, node, &mut HashMap::new())
+    }
+
+    // `0 / 0` or `inf / inf`: by growth classes at infinity, and otherwise by
+    // L'Hôpital's rule, comparing the orders of the zeros by their Taylor series
+    // when that does not settle it.
+    fn quotient(&self, num: &Rc<Node>, den: &Rc<Node>, rewrites: usize) -> Result<Limit, String> {
+        let (num, den) = (&self.unsigned(num), &self.unsigned(den));
+        if let Some(limit) = self.faster(num, den) {
+            return Ok(limit);
+        }
+        let lhopital = if rewrites < MAX_REWRITES {
+            let ratio = Node::binary(Div, Node::dif(num, &self.var), Node::dif(den, &self.var));
+            self.lim(&cancel(&simplify(&ratio)), rewrites + 1)
+        } else {
+            Err(format!("could not find the limit of {} / {}", num, den))
+        };
+        lhopital.or_else(|err| self.series(num, den).or_else(|| self.dominant(num, den)).ok_or(err))
+    }
+
+    // How fast `node` grows as `x` does, as the exponents `(a, p, q)` of
+    // `exp(a * x) * x ^ p * log(x) ^ q`, compared in that order. Sums whose
+    // largest terms grow alike may cancel, so they have no class.
+    fn growth(&self, node: &Rc<Node>) -> Option<(f64, f64, f64)> {
+        let scale = |(a, p, q): (f64, f64, f64), k: f64| (a * k, p * k, q * k);
+        match &**node {
+            Var { name, .. } if *name == self.name => Some((0.0, 1.0, 0.0)),
+            Var { point: Some(point), .. } => self.growth(point),
+            _ if value(node).is_some_and(|val| val != 0.0) => Some((0.0, 0.0, 0.0)),
+            BinaryOperator { kind: kind @ (Add | Sub), lhs, rhs } => {
+                let (g, h) = (self.growth(lhs)?, self.growth(rhs)?);
+                match g.partial_cmp(&h)? {
+                    std::cmp::Ordering::Greater => Some(g),
+                    std::cmp::Ordering::Less => Some(h),
+                    std::cmp::Ordering::Equal if *kind == Add && self.sign_near(lhs).ok()? == self.sign_near(rhs).ok()? => Some(g),
+                    std::cmp::Ordering::Equal => None,
+                }
+            },
+            BinaryOperator { kind: kind @ (Mul | Div), lhs, rhs } => {
+                let (g, h) = (self.growth(lhs)?, self.growth(rhs)?);
+                let k = if *kind == Mul { 1.0 } else { -1.0 };
+                Some((g.0 + k * h.0, g.1 + k * h.1, g.2 + k * h.2))
+            },
+            BinaryOperator { kind: Pow, lhs, rhs } => Some(scale(self.growth(lhs)?, value(rhs)?)),
+            UnaryOperator { kind: Neg, operand } => self.growth(operand),
+            UnaryOperator { kind: Exp, operand } => match self.leading(operand)? {
+                (_, 0.0) => Some((0.0, 0.0, 0.0)),
+                (a, 1.0) => Some((value(&a)?, 0.0, 0.0)),
+                _ => None,
+            },
+            UnaryOperator { kind: Log, operand } => match self.growth(operand)? {
+                (a, _, _) if a > 0.0 => Some((0.0, 1.0, 0.0)),
+                (a, p, _) if a == 0.0 && p > 0.0 => Some((0.0, 0.0, 1.0)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    // Compares the growth classes of numerator and denominator at infinity,
+    // where repeated L'Hôpital steps could take arbitrarily long.
+    fn faster(&self, num: &Rc<Node>, den: &Rc<Node>) -> Option<Limit> {
+        if self.at.is_some() {
+            return None;
+        }
+        match self.growth(num)?.partial_cmp(&self.growth(den)?)? {
+            std::cmp::Ordering::Less => Some(Finite(Node::num(0.0))),
+            std::cmp::Ordering::Greater => self.sign_near(&Node::binary(Div, num.clone(), den.clone())).ok().map(Inf),
+            std::cmp::Ordering::Equal => None,
+        }
+    }
+
+    // The term `c * x ^ p` that dominates `node` as `x` grows, for algebraic
+    // expressions whose leading terms do not cancel.
+    fn leading(&self, node: &Rc<Node>) -> Option<(Rc<Node>, f64)> {
+        let (c, p) = match &**node {
+            Var { name, .. } if *name == self.name => (Node::num(1.0), 1.0),
+            Var { point: Some(point), .. } => return self.leading(point),
+            Var { point: None, .. } | Num { .. } => (node.clone(), 0.0),
+            BinaryOperator { kind: kind @ (Add | Sub), lhs, rhs } => {
+                let ((a, p), (b, q)) = (self.leading(lhs)?, self.leading(rhs)?);
+                if p > q {
+                    (a, p)
+                } else if p < q && *kind == Add {
+                    (b, q)
+                } else if p < q {
+                    (simplify(&Node::unary(Neg, b)), q)
+                } else {
+                    (simplify(&Node::binary(*kind, a, b)), p)
+                }
+            },
+            BinaryOperator { kind: kind @ (Mul | Div), lhs, rhs } => {
+                let ((a, p), (b, q)) = (self.leading(lhs)?, self.leading(rhs)?);
+                (simplify(&Node::binary(*kind, a, b)), if *kind == Mul { p + q } else { p - q })
+            },
+            BinaryOperator { kind: Pow, lhs, rhs } => {
+                let e = value(rhs)?;
+                let (a, p) = self.leading(lhs)?;
+                if e.fract() != 0.0 && !value(&a).is_some_and(|a| a > 0.0) {
+                    return None;
+                }
+                (simplify(&Node::binary(Pow, a, rhs.clone())), p * e)
+            },
+            UnaryOperator { kind: Neg, operand } => {
+                let (a, p) = self.leading(operand)?;
+                (simplify(&Node::unary(Neg, a)), p)
+            },
+            _ => return None,
+        };
+        if is_zero(&c) { None } else { Some((c, p)) }
+    }
+
+    // Compares the leading terms of numerator and denominator at infinity.
+    fn dominant(&self, num: &Rc<Node>, den: &Rc<Node>) -> Option<Limit> {
+        if self.at.is_some() || self.side < 0.0 {
+            return None;
+        }
+        let ((a, p), (b, q)) = (self.leading(num)?, self.leading(den)?);
+        if p < q {
+            Some(Finite(Node::num(0.0)))
+        } else if p == q {
+            Some(self.finite(Node::binary(Div, a, b)))
+        } else {
+            self.sign_near(&Node::binary(Div, num.clone(), den.clone())).ok().map(Inf)
+        }
+    }
+
+    // The first nonzero Taylor coefficient of `node` at the point, times the
+    // factorial of its order.
+    fn order(&self, node: &Rc<Node>) -> Option<(usize, Rc<Node>)> {
+        let at = self.at.as_ref()?;
+        let mut node = node.clone();
+        for k in 0..=MAX_ORDER {
+            let coeff = simplify(&Node::substitute(&node, &self.var, at));
+            value(&coeff)?;
+            if !is_zero(&coeff) {
+                return Some((k, coeff));
+            }
+            node = simplify(&Node::dif(&node, &self.var));
+        }
+        None
+    }
+
+    fn series(&self, num: &Rc<Node>, den: &Rc<Node>) -> Option<Limit> {
+        let ((j, a), (k, b)) = (self.order(num)?, self.order(den)?);
+        match j.cmp(&k) {
+            std::cmp::Ordering::Greater => Some(Finite(Node::num(0.0))),
+            std::cmp::Ordering::Equal => Some(self.finite(Node::binary(Div, a, b))),
+            std::cmp::Ordering::Less => self.sign_near(&Node::binary(Div, num.clone(), den.clone())).ok().map(Inf),
+        }
+    }
+
+    // `inf * 0`, as a quotient with either factor on top.
+    fn product(&self, inf: &Rc<Node>, zero: &Rc<Node>, rewrites: usize) -> Result<Limit, String> {
+        self.quotient(inf, &recip(zero), rewrites).or_else(|_| self.quotient(zero, &recip(inf), rewrites))
+    }
+
+    // `inf - inf`: by the conjugate when a square root is involved, over a
+    // common denominator when that changes anything, and otherwise as
+    // `lhs * (1 - rhs / lhs)`.
+    fn difference(&self, node: &Rc<Node>, lhs: &Rc<Node>, rhs: &Rc<Node>, kind: BKind, rewrites: usize) -> Result<Limit, String> {
+        if rewrites >= MAX_REWRITES {
+            return Err(format!("could not find the limit of {}", node));
+        }
+        let sqrt_of = |node: &Rc<Node>| match &**node {
+            BinaryOperator { kind: Pow, lhs, rhs } if value(rhs) == Some(0.5) => Some(lhs.clone()),
+            UnaryOperator { kind: Neg, operand } => match &**operand {
+                BinaryOperator { kind: Pow, lhs, rhs } if value(rhs) == Some(0.5) => Some(lhs.clone()),
+                _ => None,
+            },
+            _ => None,
+        };
+        if sqrt_of(lhs).is_some() || sqrt_of(rhs).is_some() {
+            let square = |node: &Rc<Node>| sqrt_of(node).unwrap_or_else(|| Node::binary(Pow, node.clone(), Node::num(2.0)));
+            let conjugate = Node::binary(if kind == Add { Sub } else { Add }, lhs.clone(), rhs.clone());
+            let num = cancel(&Node::binary(Sub, square(lhs), square(rhs)));
+            return self.lim(&Node::binary(Div, num, conjugate), rewrites + 1);
+        }
+        let together = cancel(node);
+        if !Node::equiv(&together, node) {
+            return self.lim(&together, rewrites + 1);
+        }
+        let factor = Node::binary(kind, Node::num(1.0), Node::binary(Div, rhs.clone(), lhs.clone()));
+        self.lim(&Node::binary(Mul, lhs.clone(), simplify(&factor)), rewrites + 1)
+    }
+
+    fn power(&self, node: &Rc<Node>, base: &Rc<Node>, exponent: &Rc<Node>, rewrites: usize) -> Result<Limit, String> {
+        // `0 ^ 0`, `1 ^ inf` and `inf ^ 0` through `exp(exponent * log(base))`.
+        let exp_log = || self.lim(&Node::unary(Exp, Node::binary(Mul, exponent.clone(), Node::unary(Log, base.clone()))), rewrites);
+        let is_one = |node: &Rc<Node>| value(node).is_some_and(|val| (val - 1.0).abs() < 1e-12);
+        match (self.lim(base, rewrites)?, self.lim(exponent, rewrites)?) {
+            (Finite(b), Finite(e)) if is_zero(&b) && is_zero(&e) => exp_log(),
+            (Finite(b), Finite(e)) if is_zero(&b) => match self.sign_of(&e)? {
+                sign if sign > 0.0 => Ok(Finite(Node::num(0.0))),
+                _ => Ok(Inf(self.sign_near(node)?)),
+            },
+            (Finite(b), Finite(e)) => Ok(self.finite(Node::binary(Pow, b, e))),
+            (Finite(b), Inf(_)) if is_one(&b) => exp_log(),
+            (Finite(b), Inf(sign)) => match value(&b) {
+                Some(b) if b > 1.0 => Ok(if sign > 0.0 { Inf(1.0) } else { Finite(Node::num(0.0)) }),
+                Some(b) if b >= 0.0 => Ok(if sign > 0.0 { Finite(Node::num(0.0)) } else { Inf(1.0) }),
+                Some(_) => Err(format!("cannot determine the limit: {} oscillates", node)),
+                None => Err(format!("cannot compare {} with 1", b)),
+            },
+            (Inf(_), Finite(e)) if is_zero(&e) => exp_log(),
+            (Inf(_), Finite(e)) => match self.sign_of(&e)? {
+                sign if sign > 0.0 => Ok(Inf(self.sign_near(node)?)),
+                _ => Ok(Finite(Node::num(0.0))),
+            },
+            (Inf(s), Inf(t)) if s > 0.0 => Ok(if t > 0.0 { Inf(1.0) } else { Finite(Node::num(0.0)) }),
+            (Inf(_), Inf(_)) => Err(format!("cannot determine the limit: {} oscillates", node)),
+        }
+    }
+}
+
+// The limit of `node` as `var` approaches `point`, from above when `side` is
+// positive and from below when it is negative. Without a side both one-sided
+// limits must agree. `point` may be `inf` or `-inf`.
+pub fn limit(node: &Rc<Node>, var: &Rc<Node>, point: &Rc<Node>, side: Option<f64>) -> Result<Rc<Node>, String> {
+    let name = var_name(var)?;
+    let node = Node::resolve(node);
+    let point = simplify(point);
+    let approach = |at: Option<Rc<Node>>, side: f64| {
+        let at_val = at.as_ref().and_then(value).unwrap_or(0.0);
+        Approach { var: var.clone(), name: name.clone(), at, at_val, side }
+    };
+    let (at, infinite_side) = match &*point {
+        Const { kind: CKind::Inf } => (None, Some(1.0)),
+        UnaryOperator { kind: Neg, operand } if matches!(**operand, Const { kind: CKind::Inf }) => (None, Some(-1.0)),
+        _ if value(&point).is_none() => return Err(format!("the limit point {} is not a number", point)),
+        _ => (Some(point.clone()), None),
+    };
+    let to_node = |limit: Limit| match limit {
+        Finite(node) => simplify(&node),
+        Inf(sign) if sign > 0.0 => Node::constant(CKind::Inf),
+        Inf(_) => Node::unary(Neg, Node::constant(CKind::Inf)),
+    };
+
+    // Towards `-inf`, `x` is replaced by `-x` so that leading terms can be
+    // compared as `x` grows.
+    match infinite_side {
+        Some(side) if side > 0.0 => return approach(None, 1.0).lim(&node, 0).map(to_node),
+        Some(_) => return approach(None, 1.0).lim(&Node::substitute(&node, var, &Node::unary(Neg, var.clone())), 0).map(to_node),
+        None => {},
+    }
+    // Where the expression is defined and continuous at the point, the limit is
+    // its value there. A pole may still evaluate to a large finite number, as
+    // `tan(pi / 2)` does, so the values just beside the point must agree.
+    let direct = Node::substitute(&node, var, &point);
+    let simplified = simplify(&direct);
+    if let (Some(val), Some(at_val), false) = (generic_value(&direct), value(&point), has_indeterminate(&simplified)) {
+        let step = CONTINUITY_STEP * at_val.abs().max(1.0);
+        let beside = [at_val - step, at_val + step].map(|x| generic_value_at(&node, &name, x));
+        if beside.iter().flatten().all(|near| (near - val).abs() <= 1e-3 * val.abs().max(1.0)) {
+            return Ok(simplified);
+        }
+    }
+    if let Some(side) = side {
+        return approach(at, side).lim(&node, 0).map(to_node);
+    }
+    let below = approach(at.clone(), -1.0).lim(&node, 0)?;
+    let above = approach(at, 1.0).lim(&node, 0)?;
+    let agree = match (&below, &above) {
+        (Finite(a), Finite(b)) => {
+            Node::equiv(a, b) || matches!((value(a), value(b)), (Some(a), Some(b)) if (a - b).abs() <= 1e-9 * a.abs().max(1.0))
+        },
+        (Inf(s), Inf(t)) => s == t,
+        _ => false,
+    };
+    if !agree {
+        return Err(format!(
+            "the limit of {} at {} = {} does not exist: it is {} from below and {} from above",
+            node, name, point, describe(&below), describe(&above)
+        ));
+    }
+    Ok(to_node(above))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    fn lim(expr: &str, point: &str, side: Option<f64>) -> Result<String, String> {
+        let node = parse(&format!("var x; {}", expr));
+        limit(&node, &Node::var("x", None), &parse(point), side).map(|node| node.to_string())
+    }
+
+    #[test]
+    fn standard_limits() {
+        assert_eq!(lim("sin(x) / x", "0", None), Ok("1".to_string()));
+        assert_eq!(lim("(1 + 1 / x) ^ x", "inf", None), Ok("e".to_string()));
+        assert_eq!(lim("x ^ x", "0", Some(1.0)), Ok("1".to_string()));
+        assert_eq!(lim("x ^ 2 + 3", "2", None), Ok("7".to_string()));
+    }
+
+    #[test]
+    fn one_sided_poles() {
+        assert_eq!(lim("1 / x", "0", Some(1.0)), Ok("inf".to_string()));
+        assert_eq!(lim("1 / x", "0", Some(-1.0)), Ok("-inf".to_string()));
+        assert!(lim("1 / x", "0", None).unwrap_err().contains("does not exist"));
+        // `tan(pi / 2)` evaluates to a large finite number, but is a pole.
+        assert!(lim("tan(x)", "pi / 2", None).unwrap_err().contains("does not exist"));
+    }
+
+    #[test]
+    fn growth_classes() {
+        assert_eq!(lim("exp(x) / x ^ 9", "inf", None), Ok("inf".to_string()));
+        assert_eq!(lim("x ^ 9 / exp(x)", "inf", None), Ok("0".to_string()));
+        assert_eq!(lim("log(x) / x", "inf", None), Ok("0".to_string()));
+    }
+
+    #[test]
+    fn bounded_times_zero() {
+        assert_eq!(lim("exp(-x) * sin(x)", "inf", None), Ok("0".to_string()));
+        assert_eq!(lim("x * sin(1 / x)", "0", None), Ok("0".to_string()));
+        assert_eq!(lim("sin(x) / x", "inf", None), Ok("0".to_string()));
+        assert!(lim("sin(x)", "inf", None).unwrap_err().contains("cannot determine"));
+    }
+}
@@ -1,21 +1,66 @@
-pub mod node;
-pub mod lexer;
-pub mod parser;
+mod node;
+mod lexer;
+mod parser;
+mod eval;
+mod reverse;
+mod dual;
+mod complex;
+mod cse;
+mod codegen;
+mod simplify;
+mod bytecode;
+mod table;
+mod plot;
+mod root;
+mod rational;
+mod poly;
+mod solve;
+mod modular;
+mod factor;
+mod apart;
+mod limit;
+mod matrix;
+mod vector;
+mod implicit;
+mod lagrange;
 
 use std::env;
 use std::fs;
+use std::process;
 use crate::lexer::Lexer;
 use crate::parser::Parser;
 
+const PRINT_MODES: [&str; 3] = ["cse", "numpy", "sympy"];
+
 fn main() {
     let args: Vec<String> = env::args().collect();
-    let file_path = &args[1];
+    let mut format = None;
+    let mut file_path = None;
+    let mut i = 1;
+    while i < args.len() {
+        if args[i] == "--format" && i + 1 < args.len() {
+            format = Some(args[i + 1].clone());
+            i += 2;
+            continue;
+        }
+        file_path = Some(args[i].clone());
+        i += 1;
+    }
+    let Some(file_path) = file_path else {
+        println!("usage: sigma [--format cse|numpy|sympy] <file>");
+        process::exit(1);
+    };
 
     let code = fs::read_to_string(file_path).expect("Should have been able to read the file");
     let mut lexer = Lexer::new(code);
     let token_list = lexer.tokenize();
     let mut parser = Parser::new(&token_list);
-    if let Ok(node_list) = parser.prog() {
-        ;
+    if let Some(format) = format {
+        if !PRINT_MODES.contains(&format.as_str()) {
+            println!("\x1b[31merror\x1b[39m: unknown format '{}'", format);
+            process::exit(1);
+        }
+        parser.set_print_mode(&format);
     }
-}
\ No newline at end of file
+    let _ = parser.prog();
+}
@@ -0,0 +1,356 @@
+use std::rc::Rc;
+
+use crate::eval::*;
+use crate::factor::cancel;
+use crate::node::*;
+use crate::poly::{number, Poly};
+use crate::rational::Rational;
+use crate::simplify::simplify;
+
+use BKind::*;
+use Node::*;
+
+// Vectors and matrices are lists, a matrix being a list of rows of equal length.
+// Arithmetic on them is carried out when it is parsed, so the operators only
+// ever see scalars.
+
+// The items of a list, looking through definitions.
+pub fn items(node: &Rc<Node>) -> Option<Vec<Rc<Node>>> {
+    match &**node {
+        List { items } => Some(items.clone()),
+        Var { point: Some(point), .. } => items(point),
+        _ => None,
+    }
+}
+
+// The rows of a matrix.
+fn rows(node: &Rc<Node>) -> Option<Vec<Vec<Rc<Node>>>> {
+    let rows = items(node)?.iter().map(items).collect::<Option<Vec<Vec<Rc<Node>>>>>()?;
+    match rows.first() {
+        Some(first) if !first.is_empty() && rows.iter().all(|row| row.len() == first.len()) => Some(rows),
+        _ => None,
+    }
+}
+
+// A list of lists is taken for a matrix by the operations that need one, so rows
+// of different lengths are an error there rather than a list of vectors.
+fn check_rows(node: &Rc<Node>) -> Result<(), String> {
+    let rows = match items(node).and_then(|rows| rows.iter().map(items).collect::<Option<Vec<Vec<Rc<Node>>>>>()) {
+        Some(rows) => rows,
+        None => return Ok(()),
+    };
+    match rows.iter().find(|row| row.len() != rows[0].len()) {
+        Some(row) => Err(format!("the rows of {} differ in length: {} and {}", node, rows[0].len(), row.len())),
+        None => Ok(()),
+    }
+}
+
+fn describe(node: &Rc<Node>) -> String {
+    match (rows(node), items(node)) {
+        (Some(rows), _) => format!("a {}x{} matrix", rows.len(), rows[0].len()),
+        (None, Some(items)) => format!("a vector of {}", items.len()),
+        (None, None) => "a scalar".to_string(),
+    }
+}
+
+fn mismatch(lhs: &Rc<Node>, rhs: &Rc<Node>) -> String {
+    format!("{} and {} do not match", describe(lhs), describe(rhs))
+}
+
+fn matrix(rows: Vec<Vec<Rc<Node>>>) -> Rc<Node> {
+    Node::list(rows.into_iter().map(Node::list).collect())
+}
+
+fn square(node: &Rc<Node>) -> Result<Vec<Vec<Rc<Node>>>, String> {
+    check_rows(node)?;
+    match rows(node) {
+        Some(rows) if rows.len() == rows[0].len() => Ok(rows),
+        _ => Err(format!("expected a square matrix, found {}", describe(node))),
+    }
+}
+
+fn identity(n: usize) -> Rc<Node> {
+    matrix((0..n).map(|i| (0..n).map(|j| Node::num(if i == j { 1.0 } else { 0.0 })).collect()).collect())
+}
+
+// Simplified, since the sum is not written out by the user.
+fn dot(lhs: &[Rc<Node>], rhs: &[Rc<Node>]) -> Result<Rc<Node>, String> {
+    let mut terms = lhs.iter().zip(rhs).map(|(a, b)| binary(Mul, a.clone(), b.clone()));
+    let first = terms.next().unwrap_or_else(|| Ok(Node::num(0.0)))?;
+    Ok(simplify(&terms.try_fold(first, |sum, term| binary(Add, sum, term?))?))
+}
+
+fn column(rows: &[Vec<Rc<Node>>], j: usize) -> Vec<Rc<Node>> {
+    rows.iter().map(|row| row[j].clone()).collect()
+}
+
+// Matrix products, with vectors taken as columns on the right and rows on the
+// left; two vectors give their dot product.
+fn product(lhs: &Rc<Node>, rhs: &Rc<Node>) -> Result<Rc<Node>, String> {
+    check_rows(lhs)?;
+    check_rows(rhs)?;
+    let (a, b) = (items(lhs).unwrap(), items(rhs).unwrap());
+    match (rows(lhs), rows(rhs)) {
+        (Some(a), Some(b)) if a[0].len() == b.len() => {
+            let cols = (0..b[0].len()).map(|j| column(&b, j)).collect::<Vec<Vec<Rc<Node>>>>();
+            Ok(matrix(a.iter().map(|row| cols.iter().map(|col| dot(row, col)).collect()).collect::<Result<_, _>>()?))
+        },
+        (Some(a), None) if a[0].len() == b.len() => {
+            Ok(Node::list(a.iter().map(|row| dot(row, &b)).collect::<Result<_, _>>()?))
+        },
+        (None, Some(b)) if a.len() == b.len() => {
+            Ok(Node::list((0..b[0].len()).map(|j| dot(&a, &column(&b, j))).collect::<Result<_, _>>()?))
+        },
+        (None, None) if a.len() == b.len() => dot(&a, &b),
+        _ => Err(mismatch(lhs, rhs)),
+    }
+}
+
+// `node ^ n` for a square matrix and an integer `n`, negative powers being
+// powers of the inverse.
+fn power(node: &Rc<Node>, n: &Rc<Node>) -> Result<Rc<Node>, String> {
+    let size = square(node)?.len();
+    let n = match eval::<f64>(n, &Env::new()) {
+        Ok(n) if n.fract() == 0.0 => n,
+        _ => return Err(format!("a matrix power needs an integer exponent, found {}", n)),
+    };
+    let base = if n < 0.0 { inv(node)? } else { node.clone() };
+    (0..n.abs() as usize).try_fold(identity(size), |acc, _| product(&acc, &base))
+}
+
+// `lhs kind rhs` where either side may be a list. Sums and differences go item
+// by item, scalars scale lists, and two lists multiply as matrices.
+pub fn binary(kind: BKind, lhs: Rc<Node>, rhs: Rc<Node>) -> Result<Rc<Node>, String> {
+    match (items(&lhs), items(&rhs), kind) {
+        (None, None, _) => Ok(Node::binary(kind, lhs, rhs)),
+        (Some(a), Some(b), Add | Sub) if a.len() == b.len() => {
+            Ok(Node::list(a.into_iter().zip(b).map(|(a, b)| binary(kind, a, b)).collect::<Result<_, _>>()?))
+        },
+        (Some(_), Some(_), Mul) => product(&lhs, &rhs),
+        (Some(a), None, Mul | Div) => Ok(Node::list(a.into_iter().map(|a| binary(kind, a, rhs.clone())).collect::<Result<_, _>>()?)),
+        (None, Some(b), Mul) => Ok(Node::list(b.into_iter().map(|b| binary(kind, lhs.clone(), b)).collect::<Result<_, _>>()?)),
+        (Some(_), None, Pow) => power(&lhs, &rhs),
+        (Some(_), Some(_), Add | Sub) => Err(mismatch(&lhs, &rhs)),
+        _ => Err(format!("cannot combine {} and {} with '{}'", describe(&lhs), describe(&rhs), symbol(kind))),
+    }
+}
+
+// `lhs == rhs`, item by item when both sides are lists.
+pub fn equation(lhs: Rc<Node>, rhs: Rc<Node>) -> Result<Rc<Node>, String> {
+    match (items(&lhs), items(&rhs)) {
+        (None, None) => Ok(Node::equation(lhs, rhs)),
+        (Some(a), Some(b)) if a.len() == b.len() => {
+            Ok(Node::list(a.into_iter().zip(b).map(|(a, b)| equation(a, b)).collect::<Result<_, _>>()?))
+        },
+        (Some(_), Some(_)) => Err(mismatch(&lhs, &rhs)),
+        _ => Err(format!("cannot combine {} and {} with '=='", describe(&lhs), describe(&rhs))),
+    }
+}
+
+fn symbol(kind: BKind) -> &'static str {
+    match kind {
+        Add => "+",
+        Sub => "-",
+        Mul => "*",
+        Div => "/",
+        Pow => "^",
+    }
+}
+
+// The item by item product `lhs .* rhs`, simplified like matrix products.
+pub fn elementwise(lhs: Rc<Node>, rhs: Rc<Node>) -> Result<Rc<Node>, String> {
+    match (items(&lhs), items(&rhs)) {
+        (Some(a), Some(b)) if a.len() == b.len() => {
+            Ok(Node::list(a.into_iter().zip(b).map(|(a, b)| elementwise(a, b)).collect::<Result<_, _>>()?))
+        },
+        (Some(_), Some(_)) => Err(mismatch(&lhs, &rhs)),
+        (Some(a), None) => Ok(Node::list(a.into_iter().map(|a| elementwise(a, rhs.clone())).collect::<Result<_, _>>()?)),
+        (None, Some(b)) => Ok(Node::list(b.into_iter().map(|b| elementwise(lhs.clone(), b)).collect::<Result<_, _>>()?)),
+        (None, None) => Ok(simplify(&Node::binary(Mul, lhs, rhs))),
+    }
+}
+
+// Functions apply to each item of a list.
+pub fn unary(kind: UKind, operand: Rc<Node>) -> Rc<Node> {
+    match items(&operand) {
+        Some(items) => Node::list(items.into_iter().map(|item| unary(kind, item)).collect()),
+        None => Node::unary(kind, operand),
+    }
+}
+
+// Derivatives with respect to a list of variables: the gradient of a scalar, or
+// the Jacobian of a vector with one row per component.
+pub fn dif(node: &Rc<Node>, var: &Rc<Node>) -> Rc<Node> {
+    match (items(node), items(var)) {
+        (_, None) => Node::dif(node, var),
+        (Some(items), Some(_)) => Node::list(items.iter().map(|item| dif(item, var)).collect()),
+        (None, Some(vars)) => Node::list(vars.iter().map(|var| Node::dif(node, var)).collect()),
+    }
+}
+
+pub fn transpose(node: &Rc<Node>) -> Result<Rc<Node>, String> {
+    check_rows(node)?;
+    match (rows(node), items(node)) {
+        (Some(rows), _) => Ok(matrix((0..rows[0].len()).map(|j| column(&rows, j)).collect())),
+        // Vectors have no orientation of their own.
+        (None, Some(_)) => Ok(node.clone()),
+        (None, None) => Err(format!("expected a matrix, found {}", describe(node))),
+    }
+}
+
+pub fn trace(node: &Rc<Node>) -> Result<Rc<Node>, String> {
+    let rows = square(node)?;
+    let diagonal = rows.iter().enumerate().map(|(i, row)| row[i].clone()).collect::<Vec<Rc<Node>>>();
+    let sum = diagonal[1..].iter().fold(diagonal[0].clone(), |sum, item| Node::binary(Add, sum, item.clone()));
+    Ok(simplify(&sum))
+}
+
+// Fraction-free Gauss-Jordan elimination on the first `n` columns: every row
+// update `(pivot * a - factor * b) / prev` divides by the previous pivot, which
+// is exact. Afterwards each of the first `n` diagonal entries is the
+// determinant, negated when the rows were swapped an odd number of times, which
+// is what this returns. `None` means the matrix is singular.
+fn eliminate<T: Clone>(
+    rows: &mut [Vec<T>],
+    n: usize,
+    zero: T,
+    one: T,
+    is_zero: impl Fn(&T) -> bool,
+    update: impl Fn(&T, &T, &T, &T, &T) -> Result<T, String>,
+) -> Result<Option<bool>, String> {
+    let mut prev = one;
+    let mut negated = false;
+    for k in 0..n {
+        let Some(r) = (k..n).find(|r| !is_zero(&rows[*r][k])) else { return Ok(None) };
+        if r != k {
+            rows.swap(k, r);
+            negated = !negated;
+        }
+        let pivot = rows[k][k].clone();
+        for i in (0..n).filter(|i| *i != k) {
+            let factor = rows[i][k].clone();
+            rows[i][k] = zero.clone();
+            for j in (0..rows[i].len()).filter(|j| *j != k) {
+                rows[i][j] = update(&pivot, &rows[i][j], &factor, &rows[k][j], &prev)?;
+            }
+        }
+        prev = pivot;
+    }
+    Ok(Some(negated))
+}
+
+// Matrices of constants are eliminated with exact fractions; the result is
+// only rounded if no `f32`, or quotient of two, holds it exactly.
+fn exact(rows: &[Vec<Rc<Node>>]) -> Option<Vec<Vec<Rational>>> {
+    rows.iter().map(|row| row.iter().map(|item| Poly::from_node(item).ok()?.as_constant()).collect()).collect()
+}
+
+fn rational_update(pivot: &Rational, a: &Rational, factor: &Rational, b: &Rational, prev: &Rational) -> Result<Rational, String> {
+    pivot.checked_mul(*a)?.checked_add(-factor.checked_mul(*b)?)?.checked_div(*prev)
+}
+
+fn constant(c: Rational) -> Rc<Node> {
+    match (number(c), number(Rational::int(c.num)), number(Rational::int(c.den))) {
+        (Ok(node), ..) => node,
+        (_, Ok(num), Ok(den)) => Node::binary(Div, num, den),
+        _ => Node::num(c.num as f32 / c.den as f32),
+    }
+}
+
+// Other entries are kept cancelled, so that an entry that vanishes as a
+// rational function is literally `0`.
+fn is_zero(node: &Rc<Node>) -> bool {
+    matches!(**node, Num { val } if val == 0.0)
+}
+
+fn symbolic_update(pivot: &Rc<Node>, a: &Rc<Node>, factor: &Rc<Node>, b: &Rc<Node>, prev: &Rc<Node>) -> Result<Rc<Node>, String> {
+    let entry = Node::binary(Sub, Node::binary(Mul, pivot.clone(), a.clone()), Node::binary(Mul, factor.clone(), b.clone()));
+    Ok(cancel(&Node::binary(Div, entry, prev.clone())))
+}
+
+pub fn det(node: &Rc<Node>) -> Result<Rc<Node>, String> {
+    let rows = square(node)?;
+    let n = rows.len();
+    if let Some(mut rows) = exact(&rows) {
+        let det = match eliminate(&mut rows, n, Rational::int(0), Rational::int(1), Rational::is_zero, rational_update)? {
+            Some(negated) => if negated { -rows[n - 1][n - 1] } else { rows[n - 1][n - 1] },
+            None => Rational::int(0),
+        };
+        return Ok(constant(det));
+    }
+    let mut rows = rows.iter().map(|row| row.iter().map(cancel).collect()).collect::<Vec<Vec<Rc<Node>>>>();
+    Ok(match eliminate(&mut rows, n, Node::num(0.0), Node::num(1.0), is_zero, symbolic_update)? {
+        Some(false) => rows[n - 1][n - 1].clone(),
+        Some(true) => cancel(&Node::unary(UKind::Neg, rows[n - 1][n - 1].clone())),
+        None => Node::num(0.0),
+    })
+}
+
+// Eliminating on the matrix with the identity beside it leaves `det * I` on the
+// left and `det` times the inverse on the right.
+pub fn inv(node: &Rc<Node>) -> Result<Rc<Node>, String> {
+    let rows = square(node)?;
+    let n = rows.len();
+    let singular = || format!("{} is singular", node);
+    let inverse = if let Some(rows) = exact(&rows) {
+        let mut rows = rows.into_iter().enumerate().map(|(i, mut row)| {
+            row.extend((0..n).map(|j| Rational::int(if i == j { 1 } else { 0 })));
+            row
+        }).collect::<Vec<Vec<Rational>>>();
+        if eliminate(&mut rows, n, Rational::int(0), Rational::int(1), Rational::is_zero, rational_update)?.is_none() {
+            return Err(singular());
+        }
+        rows.iter().enumerate().map(|(i, row)| {
+            row[n..].iter().map(|item| Ok(constant(item.checked_div(row[i])?))).collect()
+        }).collect::<Result<Vec<Vec<Rc<Node>>>, String>>()?
+    } else {
+        let mut rows = rows.iter().enumerate().map(|(i, row)| {
+            row.iter().map(cancel).chain((0..n).map(|j| Node::num(if i == j { 1.0 } else { 0.0 }))).collect()
+        }).collect::<Vec<Vec<Rc<Node>>>>();
+        if eliminate(&mut rows, n, Node::num(0.0), Node::num(1.0), is_zero, symbolic_update)?.is_none() {
+            return Err(singular());
+        }
+        rows.iter().enumerate().map(|(i, row)| {
+            row[n..].iter().map(|item| cancel(&Node::binary(Div, item.clone(), row[i].clone()))).collect()
+        }).collect()
+    };
+    Ok(matrix(inverse))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    #[test]
+    fn ragged_rows() {
+        let ragged = parse("[[1, 2], [3]]");
+        let error = "the rows of [[1, 2], [3]] differ in length: 2 and 1";
+        assert_eq!(det(&ragged).unwrap_err(), error);
+        assert_eq!(transpose(&ragged).unwrap_err(), error);
+        assert_eq!(binary(Mul, ragged.clone(), parse("[[1], [2]]")).unwrap_err(), error);
+        // Item by item, rows need not be alike.
+        assert_eq!(binary(Mul, Node::num(2.0), ragged).unwrap().to_string(), "[[2 * 1, 2 * 2], [2 * 3]]");
+    }
+
+    #[test]
+    fn inverse_of_a_3x3_matrix() {
+        let a = parse("[[2, 0, 1], [1, 1, 0], [0, 3, 1]]");
+        let inverse = inv(&a).unwrap();
+        assert_eq!(inverse, simplify(&parse("[[0.2, 0.6, -0.2], [-0.2, 0.4, 0.2], [0.6, -1.2, 0.4]]")));
+        let product = rows(&binary(Mul, a.clone(), inverse).unwrap()).unwrap();
+        for (i, row) in product.iter().enumerate() {
+            for (j, item) in row.iter().enumerate() {
+                let expected = if i == j { 1.0 } else { 0.0 };
+                assert!((eval::<f64>(item, &Env::new()).unwrap() - expected).abs() < 1e-6);
+            }
+        }
+        assert_eq!(det(&a).unwrap().to_string(), "5");
+        assert!(inv(&parse("[[1, 2, 3], [4, 5, 6], [7, 8, 9]]")).is_err());
+    }
+
+    #[test]
+    fn symbolic_determinant() {
+        assert_eq!(det(&parse("var a, b; [[a, b], [b, a]]")).unwrap().to_string(), "a ^ 2 - b ^ 2");
+    }
+}
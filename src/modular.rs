@@ -0,0 +1,390 @@
+// Factorisation of square-free integer polynomials by the Zassenhaus algorithm:
+// factor modulo a small prime, lift the factors to a power of that prime large
+// enough to contain the true coefficients, then recombine the lifted factors.
+// Polynomials are coefficient vectors, lowest power first.
+
+use crate::rational;
+
+type ZPoly = Vec<i128>;
+
+const PRIMES: [i128; 24] = [3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97];
+// Lifting stops short of this so that products of two residues fit in an i128.
+const MAX_MODULUS: f64 = 1e18;
+const MAX_DEGREE: usize = 120;
+// Primes for gcds, still small enough for products of residues to fit.
+const GCD_PRIMES: [i128; 3] = [2305843009213693951, 1000000000000000003, 1000000000000000009];
+
+fn trim(mut a: ZPoly) -> ZPoly {
+    while a.len() > 1 && *a.last().unwrap() == 0 {
+        a.pop();
+    }
+    a
+}
+
+fn degree(a: &ZPoly) -> usize {
+    a.len() - 1
+}
+
+fn is_zero(a: &ZPoly) -> bool {
+    a.iter().all(|c| *c == 0)
+}
+
+fn reduce(a: &ZPoly, m: i128) -> ZPoly {
+    trim(a.iter().map(|c| c.rem_euclid(m)).collect())
+}
+
+fn add(a: &ZPoly, b: &ZPoly, m: i128) -> ZPoly {
+    let n = a.len().max(b.len());
+    trim((0..n).map(|i| (a.get(i).unwrap_or(&0) + b.get(i).unwrap_or(&0)).rem_euclid(m)).collect())
+}
+
+fn sub(a: &ZPoly, b: &ZPoly, m: i128) -> ZPoly {
+    let n = a.len().max(b.len());
+    trim((0..n).map(|i| (a.get(i).unwrap_or(&0) - b.get(i).unwrap_or(&0)).rem_euclid(m)).collect())
+}
+
+fn mul(a: &ZPoly, b: &ZPoly, m: i128) -> ZPoly {
+    let mut res = vec![0; a.len() + b.len() - 1];
+    for (i, x) in a.iter().enumerate() {
+        for (j, y) in b.iter().enumerate() {
+            res[i + j] = (res[i + j] + x * y % m).rem_euclid(m);
+        }
+    }
+    trim(res)
+}
+
+fn scale(a: &ZPoly, c: i128, m: i128) -> ZPoly {
+    trim(a.iter().map(|x| (x * c).rem_euclid(m)).collect())
+}
+
+fn inverse(a: i128, m: i128) -> i128 {
+    let (mut r0, mut r1, mut s0, mut s1) = (a.rem_euclid(m), m, 1, 0);
+    while r1 != 0 {
+        let q = r0 / r1;
+        (r0, r1) = (r1, r0 - q * r1);
+        (s0, s1) = (s1, s0 - q * s1);
+    }
+    s0.rem_euclid(m)
+}
+
+fn monic(a: &ZPoly, m: i128) -> ZPoly {
+    scale(a, inverse(*a.last().unwrap(), m), m)
+}
+
+// Division by `b`, whose leading coefficient must be invertible modulo `m`.
+fn divrem(a: &ZPoly, b: &ZPoly, m: i128) -> (ZPoly, ZPoly) {
+    let mut r = reduce(a, m);
+    let db = degree(b);
+    let inv = inverse(*b.last().unwrap(), m);
+    let mut q = vec![0; (r.len() + 1).saturating_sub(b.len()).max(1)];
+    while !is_zero(&r) && degree(&r) >= db {
+        let shift = degree(&r) - db;
+        let c = (r.last().unwrap() * inv).rem_euclid(m);
+        q[shift] = c;
+        for (i, y) in b.iter().enumerate() {
+            r[i + shift] = (r[i + shift] - c * y % m).rem_euclid(m);
+        }
+        r = trim(r);
+    }
+    (trim(q), r)
+}
+
+fn gcd_mod(a: &ZPoly, b: &ZPoly, p: i128) -> ZPoly {
+    let (mut a, mut b) = (reduce(a, p), reduce(b, p));
+    while !is_zero(&b) {
+        let r = divrem(&a, &b, p).1;
+        a = b;
+        b = r;
+    }
+    if is_zero(&a) { a } else { monic(&a, p) }
+}
+
+// `s` and `t` with `s a + t b = 1` modulo `p`, for coprime `a` and `b`.
+fn bezout(a: &ZPoly, b: &ZPoly, p: i128) -> (ZPoly, ZPoly) {
+    let (mut r0, mut r1) = (reduce(a, p), reduce(b, p));
+    let (mut s0, mut s1) = (vec![1], vec![0]);
+    let (mut t0, mut t1) = (vec![0], vec![1]);
+    while !is_zero(&r1) {
+        let (q, r) = divrem(&r0, &r1, p);
+        (r0, r1) = (r1, r);
+        let s = sub(&s0, &mul(&q, &s1, p), p);
+        (s0, s1) = (s1, s);
+        let t = sub(&t0, &mul(&q, &t1, p), p);
+        (t0, t1) = (t1, t);
+    }
+    let inv = inverse(r0[0], p);
+    (scale(&s0, inv, p), scale(&t0, inv, p))
+}
+
+fn derivative(a: &ZPoly, m: i128) -> ZPoly {
+    if a.len() == 1 {
+        return vec![0];
+    }
+    trim(a.iter().enumerate().skip(1).map(|(i, c)| (c * i as i128).rem_euclid(m)).collect())
+}
+
+fn powmod(a: &ZPoly, mut e: u64, f: &ZPoly, p: i128) -> ZPoly {
+    let mut base = divrem(a, f, p).1;
+    let mut res = vec![1];
+    while e > 0 {
+        if e & 1 == 1 {
+            res = divrem(&mul(&res, &base, p), f, p).1;
+        }
+        base = divrem(&mul(&base, &base, p), f, p).1;
+        e >>= 1;
+    }
+    res
+}
+
+// Distinct-degree factorisation of a monic square-free `f`: products of all
+// irreducible factors of each degree.
+fn distinct_degree(f: &ZPoly, p: i128) -> Vec<(ZPoly, usize)> {
+    let mut f = f.clone();
+    let mut h = vec![0, 1];
+    let mut res = Vec::new();
+    let mut d = 1;
+    while degree(&f) >= 2 * d {
+        h = powmod(&h, p as u64, &f, p);
+        let g = gcd_mod(&f, &sub(&h, &vec![0, 1], p), p);
+        if degree(&g) > 0 {
+            f = divrem(&f, &g, p).0;
+            h = divrem(&h, &f, p).1;
+            res.push((g, d));
+        }
+        d += 1;
+    }
+    if degree(&f) > 0 {
+        let d = degree(&f);
+        res.push((f, d));
+    }
+    res
+}
+
+// Cantor-Zassenhaus splitting of a product of irreducibles of degree `d`. The
+// random polynomials come from a fixed seed, so results are reproducible.
+fn equal_degree(f: &ZPoly, d: usize, p: i128, seed: &mut u64) -> Vec<ZPoly> {
+    if degree(f) == d {
+        return vec![f.clone()];
+    }
+    loop {
+        let a = trim((0..degree(f)).map(|_| {
+            *seed ^= *seed << 13;
+            *seed ^= *seed >> 7;
+            *seed ^= *seed << 17;
+            (*seed % p as u64) as i128
+        }).collect());
+        if degree(&a) == 0 {
+            continue;
+        }
+        // a^((p^d - 1) / 2) = (a a^p ... a^(p^(d-1)))^((p - 1) / 2)
+        let mut power = divrem(&a, f, p).1;
+        let mut product = power.clone();
+        for _ in 1..d {
+            power = powmod(&power, p as u64, f, p);
+            product = divrem(&mul(&product, &power, p), f, p).1;
+        }
+        let t = powmod(&product, ((p - 1) / 2) as u64, f, p);
+        let g = gcd_mod(f, &sub(&t, &vec![1], p), p);
+        if degree(&g) > 0 && degree(&g) < degree(f) {
+            let mut res = equal_degree(&g, d, p, seed);
+            res.extend(equal_degree(&divrem(f, &g, p).0, d, p, seed));
+            return res;
+        }
+    }
+}
+
+// The monic irreducible factors of `f` modulo `p`.
+fn factor_mod(f: &ZPoly, p: i128) -> Vec<ZPoly> {
+    let mut seed = 0x2545f4914f6cdd1d;
+    distinct_degree(&monic(&reduce(f, p), p), p)
+        .iter()
+        .flat_map(|(g, d)| equal_degree(g, *d, p, &mut seed))
+        .collect()
+}
+
+// Lifts `f = g h (mod p)` with monic `g` to `f = G H (mod p^k)`, one power of `p`
+// at a time.
+fn hensel(f: &ZPoly, g: &ZPoly, h: &ZPoly, p: i128, k: u32) -> (ZPoly, ZPoly) {
+    let m = p.pow(k);
+    let (s, t) = bezout(g, h, p);
+    let (mut g, mut h) = (g.clone(), h.clone());
+    for j in 1..k {
+        let pj = p.pow(j);
+        let diff = sub(&reduce(f, m), &mul(&g, &h, m), m);
+        let e = reduce(&diff.iter().map(|c| c / pj).collect(), p);
+        let (q, tau) = divrem(&mul(&t, &e, p), &g, p);
+        let sigma = add(&mul(&s, &e, p), &mul(&q, &h, p), p);
+        g = add(&g, &scale(&tau, pj, m), m);
+        h = add(&h, &scale(&sigma, pj, m), m);
+    }
+    (g, h)
+}
+
+// Lifts the factorisation `f = lc(f) g1 ... gr (mod p)` to monic factors
+// modulo `p^k`.
+fn lift(f: &ZPoly, factors: &[ZPoly], p: i128, k: u32) -> Vec<ZPoly> {
+    let m = p.pow(k);
+    if factors.len() == 1 {
+        return vec![monic(&reduce(f, m), m)];
+    }
+    let rest = factors[1..].iter().fold(vec![*f.last().unwrap()], |acc, g| mul(&acc, g, p));
+    let (g, h) = hensel(f, &factors[0], &rest, p, k);
+    let mut res = vec![g];
+    res.extend(lift(&h, &factors[1..], p, k));
+    res
+}
+
+fn symmetric(a: &ZPoly, m: i128) -> ZPoly {
+    trim(a.iter().map(|c| if *c > m / 2 { c - m } else { *c }).collect())
+}
+
+fn primitive(a: &ZPoly) -> ZPoly {
+    let content = a.iter().fold(0, |acc, c| rational::gcd(acc, *c)) * a.last().unwrap().signum();
+    a.iter().map(|c| c / content).collect()
+}
+
+// `a / b` over the integers, or `None` when `b` does not divide `a`.
+fn divide(a: &ZPoly, b: &ZPoly) -> Option<ZPoly> {
+    let mut r = a.clone();
+    let db = degree(b);
+    let lb = *b.last().unwrap();
+    if degree(&r) < db {
+        return None;
+    }
+    let mut q = vec![0; degree(&r) - db + 1];
+    for shift in (0..q.len()).rev() {
+        let c = r[shift + db];
+        if c % lb != 0 {
+            return None;
+        }
+        let c = c / lb;
+        q[shift] = c;
+        for (i, y) in b.iter().enumerate() {
+            r[i + shift] = r[i + shift].checked_sub(c.checked_mul(*y)?)?;
+        }
+    }
+    if r.iter().all(|c| *c == 0) { Some(q) } else { None }
+}
+
+// The gcd of two primitive integer polynomials of positive degree, computed
+// modulo a large prime and checked by division, or `None` when no prime gives
+// a common divisor. Unlike a remainder sequence over the integers, this keeps
+// the coefficients small.
+pub fn gcd(a: &[i128], b: &[i128]) -> Option<ZPoly> {
+    let (a, b) = (trim(a.to_vec()), trim(b.to_vec()));
+    let lc = rational::gcd(*a.last().unwrap(), *b.last().unwrap());
+    for p in GCD_PRIMES.iter().filter(|p| *a.last().unwrap() % *p != 0 && *b.last().unwrap() % *p != 0) {
+        let g = gcd_mod(&a, &b, *p);
+        if degree(&g) == 0 {
+            return Some(vec![1]);
+        }
+        let g = primitive(&symmetric(&scale(&g, lc.rem_euclid(*p), *p), *p));
+        if divide(&a, &g).is_some() && divide(&b, &g).is_some() {
+            return Some(g);
+        }
+    }
+    None
+}
+
+pub fn combinations(n: usize, k: usize) -> Vec<Vec<usize>> {
+    if k == 0 {
+        return vec![Vec::new()];
+    }
+    if n < k {
+        return Vec::new();
+    }
+    let mut res = combinations(n - 1, k);
+    for mut combination in combinations(n - 1, k - 1) {
+        combination.push(n - 1);
+        res.push(combination);
+    }
+    res
+}
+
+// The irreducible factors over the integers of a primitive, square-free `f` of
+// positive degree, each primitive with a positive leading coefficient. Too large
+// an `f` is returned whole.
+pub fn factor(f: &[i128]) -> Vec<ZPoly> {
+    let f = primitive(&trim(f.to_vec()));
+    let n = degree(&f);
+    if n <= 1 || n > MAX_DEGREE {
+        return vec![f];
+    }
+    let lc = *f.last().unwrap();
+
+    let mut best: Option<(i128, Vec<ZPoly>)> = None;
+    for p in PRIMES.iter().filter(|p| lc % *p != 0) {
+        let fp = reduce(&f, *p);
+        if degree(&gcd_mod(&fp, &derivative(&fp, *p), *p)) > 0 {
+            continue;
+        }
+        let factors = factor_mod(&f, *p);
+        if best.as_ref().is_none_or(|(_, best)| factors.len() < best.len()) {
+            best = Some((*p, factors));
+        }
+        if best.as_ref().is_some_and(|(_, best)| best.len() == 1) {
+            break;
+        }
+    }
+    let Some((p, factors)) = best else { return vec![f] };
+    if factors.len() == 1 {
+        return vec![f];
+    }
+
+    // Every coefficient of a factor of `lc f` is bounded by the Mignotte bound.
+    let norm = f.iter().map(|c| (*c as f64).powi(2)).sum::<f64>().sqrt();
+    let bound = 2f64.powi(n as i32) * norm * (lc as f64).abs();
+    let mut k = 1;
+    while (p as f64).powi(k as i32) <= 2.0 * bound {
+        k += 1;
+    }
+    if (p as f64).powi(k as i32) > MAX_MODULUS {
+        return vec![f];
+    }
+    let m = p.pow(k);
+    let mut lifted = lift(&f, &factors, p, k);
+
+    let mut f = f;
+    let mut res = Vec::new();
+    let mut size = 1;
+    'grow: while 2 * size <= lifted.len() {
+        for subset in combinations(lifted.len(), size) {
+            let lc = *f.last().unwrap();
+            let candidate = subset.iter().fold(vec![lc.rem_euclid(m)], |acc, i| mul(&acc, &lifted[*i], m));
+            let candidate = primitive(&symmetric(&candidate, m));
+            if let Some(quotient) = divide(&f, &candidate) {
+                res.push(candidate);
+                f = quotient;
+                lifted = lifted.into_iter().enumerate().filter(|(i, _)| !subset.contains(i)).map(|(_, g)| g).collect();
+                continue 'grow;
+            }
+        }
+        size += 1;
+    }
+    res.push(primitive(&f));
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gcd_of_integer_polynomials() {
+        // (x - 1)(x + 2) and (x - 1)(x + 3).
+        assert_eq!(gcd(&[-2, 1, 1], &[-3, 2, 1]), Some(vec![-1, 1]));
+        assert_eq!(gcd(&[1, 0, 1], &[-1, 1]), Some(vec![1]));
+        // Large coefficients do not overflow: (x - 10^12)(x + 1) and (x - 10^12)(x - 1).
+        let r = 1_000_000_000_000;
+        assert_eq!(gcd(&[-r, 1 - r, 1], &[r, -1 - r, 1]), Some(vec![-r, 1]));
+    }
+
+    #[test]
+    fn zassenhaus_recombines_lifted_factors() {
+        // x^4 + 1 splits modulo every prime but is irreducible.
+        assert_eq!(factor(&[1, 0, 0, 0, 1]), vec![vec![1, 0, 0, 0, 1]]);
+        let mut factors = factor(&[-1, 0, 0, 0, 0, 0, 1]);
+        factors.sort();
+        assert_eq!(factors, vec![vec![-1, 1], vec![1, -1, 1], vec![1, 1], vec![1, 1, 1]]);
+    }
+}
@@ -1,8 +1,14 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+
 use BKind::*;
 use UKind::*;
 use Node::*;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum BKind {
     Add,
     Sub,
@@ -11,7 +17,7 @@ pub enum BKind {
     Pow,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum UKind {
     Neg,
     Sin,
@@ -19,205 +25,587 @@ pub enum UKind {
     Tan,
     Exp,
     Log,
+    Asin,
+    // Parts of complex values. Variables are real, so these only act on
+    // expressions involving `i`.
+    Re,
+    Im,
+    Conj,
+    Arg,
+    Abs,
+}
+
+// Named constants, which stay symbolic until evaluated. Identifiers that are
+// not otherwise defined name them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CKind {
+    Pi,
+    E,
+    Inf,
+    // The imaginary unit.
+    I,
+}
+
+impl CKind {
+    pub fn from_name(name: &str) -> Option<CKind> {
+        match name {
+            "pi" => Some(CKind::Pi),
+            "e" => Some(CKind::E),
+            "inf" => Some(CKind::Inf),
+            "i" => Some(CKind::I),
+            _ => None,
+        }
+    }
+
+    // The real value, which `i` does not have.
+    pub fn value(&self) -> Option<f64> {
+        match self {
+            CKind::Pi => Some(std::f64::consts::PI),
+            CKind::E => Some(std::f64::consts::E),
+            CKind::Inf => Some(f64::INFINITY),
+            CKind::I => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CKind::Pi => "pi",
+            CKind::E => "e",
+            CKind::Inf => "inf",
+            CKind::I => "i",
+        }
+    }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+// Nodes are hash-consed: every node is built through `Node::binary`, `Node::unary`,
+// `Node::var`, `Node::dependent`, `Node::derivative`, `Node::num`, `Node::constant`, `Node::list`,
+// `Node::equation` or `Node::unsolved`, so structurally identical subexpressions share one
+// allocation and children can be compared by pointer.
+#[derive(Debug, Clone)]
 pub enum Node {
-    BinaryOperator { kind: BKind, lhs: Box<Node>, rhs: Box<Node> },
-    UnaryOperator { kind: UKind, operand: Box<Node> },
-    Var { name: String, point: Option<Box<Node>> },
+    BinaryOperator { kind: BKind, lhs: Rc<Node>, rhs: Rc<Node> },
+    UnaryOperator { kind: UKind, operand: Rc<Node> },
+    // `deps` names the variables an unknown function such as `y(t)` depends on.
+    Var { name: String, point: Option<Rc<Node>>, deps: Vec<String> },
+    // `dif(of, var)` of an unknown function, which has no closed form.
+    Derivative { of: Rc<Node>, var: Rc<Node> },
     Num { val: f32 },
+    Const { kind: CKind },
+    List { items: Vec<Rc<Node>> },
+    // `lhs == rhs`, which `solve` takes apart. It has no value of its own.
+    Equation { lhs: Rc<Node>, rhs: Rc<Node> },
+    // `solve(equation, var)` left as it is because no closed form was found.
+    Unsolved { equation: Rc<Node>, var: Rc<Node> },
 }
 
-impl Node {
-    fn equiv(node1: Node, node2: Node) -> bool {
-        match node1 {
-            BinaryOperator { kind: kind1, lhs: lhs1, rhs: rhs1 } => {
-                match node2 {
-                    BinaryOperator { kind: kind2, lhs: lhs2, rhs: rhs2 } => {
-                        kind1 == kind2 && Node::equiv(*lhs1, *lhs2) && Node::equiv(*rhs1, *rhs2)
-                    },
-                    _ => false
-                }
+impl PartialEq for Node {
+    fn eq(&self, other: &Node) -> bool {
+        match (self, other) {
+            (BinaryOperator { kind: kind1, lhs: lhs1, rhs: rhs1 }, BinaryOperator { kind: kind2, lhs: lhs2, rhs: rhs2 }) => {
+                kind1 == kind2 && Rc::ptr_eq(lhs1, lhs2) && Rc::ptr_eq(rhs1, rhs2)
             },
-            UnaryOperator { kind: kind1, operand: operand1 } => {
-                match node2 {
-                    UnaryOperator { kind: kind2, operand: operand2 } => {
-                        kind1 == kind2 && Node::equiv(*operand1, *operand2)
-                    },
-                    _ => false
-                }
+            (UnaryOperator { kind: kind1, operand: operand1 }, UnaryOperator { kind: kind2, operand: operand2 }) => {
+                kind1 == kind2 && Rc::ptr_eq(operand1, operand2)
             },
-            Var { name: name1, .. } => {
-                match node2 {
-                    Var { name: name2, .. } => { name1 == name2 },
-                    _ => false
+            (Var { name: name1, point: point1, deps: deps1 }, Var { name: name2, point: point2, deps: deps2 }) => {
+                name1 == name2 && deps1 == deps2 && match (point1, point2) {
+                    (Some(point1), Some(point2)) => Rc::ptr_eq(point1, point2),
+                    (None, None) => true,
+                    _ => false,
                 }
             },
-            Num { val: val1 } => {
-                match node2 {
-                    Num { val: val2 } => { val1 == val2 },
-                    _ => false
+            (Derivative { of: of1, var: var1 }, Derivative { of: of2, var: var2 }) => {
+                Rc::ptr_eq(of1, of2) && Rc::ptr_eq(var1, var2)
+            },
+            (Num { val: val1 }, Num { val: val2 }) => val1.to_bits() == val2.to_bits(),
+            (Const { kind: kind1 }, Const { kind: kind2 }) => kind1 == kind2,
+            (List { items: items1 }, List { items: items2 }) => {
+                items1.len() == items2.len() && items1.iter().zip(items2).all(|(item1, item2)| Rc::ptr_eq(item1, item2))
+            },
+            (Equation { lhs: lhs1, rhs: rhs1 }, Equation { lhs: lhs2, rhs: rhs2 }) => {
+                Rc::ptr_eq(lhs1, lhs2) && Rc::ptr_eq(rhs1, rhs2)
+            },
+            (Unsolved { equation: equation1, var: var1 }, Unsolved { equation: equation2, var: var2 }) => {
+                Rc::ptr_eq(equation1, equation2) && Rc::ptr_eq(var1, var2)
+            },
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Node {}
+
+impl Hash for Node {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            BinaryOperator { kind, lhs, rhs } => {
+                kind.hash(state);
+                Rc::as_ptr(lhs).hash(state);
+                Rc::as_ptr(rhs).hash(state);
+            },
+            UnaryOperator { kind, operand } => {
+                kind.hash(state);
+                Rc::as_ptr(operand).hash(state);
+            },
+            Var { name, point, deps } => {
+                name.hash(state);
+                point.as_ref().map(Rc::as_ptr).hash(state);
+                deps.hash(state);
+            },
+            Derivative { of, var } => {
+                Rc::as_ptr(of).hash(state);
+                Rc::as_ptr(var).hash(state);
+            },
+            Num { val } => {
+                val.to_bits().hash(state);
+            },
+            Const { kind } => {
+                kind.hash(state);
+            },
+            List { items } => {
+                for item in items {
+                    Rc::as_ptr(item).hash(state);
                 }
             },
+            Equation { lhs, rhs } => {
+                Rc::as_ptr(lhs).hash(state);
+                Rc::as_ptr(rhs).hash(state);
+            },
+            Unsolved { equation, var } => {
+                Rc::as_ptr(equation).hash(state);
+                Rc::as_ptr(var).hash(state);
+            },
+        }
+    }
+}
+
+thread_local! {
+    // Interned nodes are never freed, so their addresses stay valid as memo keys.
+    static NODES: RefCell<HashSet<Rc<Node>>> = RefCell::new(HashSet::new());
+    static DIF_MEMO: RefCell<HashMap<(*const Node, *const Node), Rc<Node>>> = RefCell::new(HashMap::new());
+}
+
+impl Node {
+    fn intern(node: Node) -> Rc<Node> {
+        NODES.with(|nodes| {
+            let mut nodes = nodes.borrow_mut();
+            if let Some(shared) = nodes.get(&node) {
+                return shared.clone();
+            }
+            let shared = Rc::new(node);
+            nodes.insert(shared.clone());
+            shared
+        })
+    }
+
+    pub fn binary(kind: BKind, lhs: Rc<Node>, rhs: Rc<Node>) -> Rc<Node> {
+        Node::intern(BinaryOperator { kind, lhs, rhs })
+    }
+
+    pub fn unary(kind: UKind, operand: Rc<Node>) -> Rc<Node> {
+        Node::intern(UnaryOperator { kind, operand })
+    }
+
+    pub fn var(name: &str, point: Option<Rc<Node>>) -> Rc<Node> {
+        Node::intern(Var { name: name.to_string(), point, deps: Vec::new() })
+    }
+
+    // An unknown function of the variables named in `deps`.
+    pub fn dependent(name: &str, deps: Vec<String>) -> Rc<Node> {
+        Node::intern(Var { name: name.to_string(), point: None, deps })
+    }
+
+    pub fn derivative(of: Rc<Node>, var: Rc<Node>) -> Rc<Node> {
+        Node::intern(Derivative { of, var })
+    }
+
+    // Whether the unknown function behind `node` depends on `var`.
+    fn depends_on(node: &Node, var: &Node) -> bool {
+        match (node, var) {
+            (Var { deps, .. }, Var { name, point: None, .. }) => deps.contains(name),
+            (Derivative { of, .. }, _) => Node::depends_on(of, var),
+            _ => false,
+        }
+    }
+
+    pub fn num(val: f32) -> Rc<Node> {
+        Node::intern(Num { val })
+    }
+
+    pub fn constant(kind: CKind) -> Rc<Node> {
+        Node::intern(Const { kind })
+    }
+
+    pub fn list(items: Vec<Rc<Node>>) -> Rc<Node> {
+        Node::intern(List { items })
+    }
+
+    pub fn equation(lhs: Rc<Node>, rhs: Rc<Node>) -> Rc<Node> {
+        Node::intern(Equation { lhs, rhs })
+    }
+
+    pub fn unsolved(equation: Rc<Node>, var: Rc<Node>) -> Rc<Node> {
+        Node::intern(Unsolved { equation, var })
+    }
+
+    pub fn equiv(node1: &Rc<Node>, node2: &Rc<Node>) -> bool {
+        Rc::ptr_eq(node1, node2)
+    }
+
+    pub fn dif(node1: &Rc<Node>, node2: &Rc<Node>) -> Rc<Node> {
+        let key = (Rc::as_ptr(node1), Rc::as_ptr(node2));
+        if let Some(memo) = DIF_MEMO.with(|memo| memo.borrow().get(&key).cloned()) {
+            return memo;
         }
+        let res = Node::dif_uncached(node1, node2);
+        DIF_MEMO.with(|memo| memo.borrow_mut().insert(key, res.clone()));
+        res
     }
 
-    pub fn dif(node1: Node, node2: Node) -> Node {
-        match node1 {
+    fn dif_uncached(node1: &Rc<Node>, node2: &Rc<Node>) -> Rc<Node> {
+        match &**node1 {
             BinaryOperator { kind, lhs, rhs } => {
                 match kind {
-                    Add => BinaryOperator { kind: Add, lhs: Box::new(Node::dif(*lhs, node2.clone())), rhs: Box::new(Node::dif(*rhs, node2.clone())) },
-                    Sub => BinaryOperator { kind: Sub, lhs: Box::new(Node::dif(*lhs, node2.clone())), rhs: Box::new(Node::dif(*rhs, node2.clone())) },
-                    Mul => BinaryOperator {
-                        kind: Add,
-                        lhs: Box::new(BinaryOperator { kind: Mul, lhs: Box::new(Node::dif((*lhs).clone(), node2.clone())), rhs: Box::new((*rhs).clone()) }),
-                        rhs: Box::new(BinaryOperator { kind: Mul, lhs: Box::new((*lhs).clone()), rhs: Box::new(Node::dif((*rhs).clone(), node2.clone())) })
-                    },
-                    Div => BinaryOperator {
-                        kind: Div,
-                        lhs: Box::new(BinaryOperator {
-                            kind: Sub,
-                            lhs: Box::new(BinaryOperator { kind: Mul, lhs: Box::new(Node::dif((*lhs).clone(), node2.clone())), rhs: Box::new((*rhs).clone()) }),
-                            rhs: Box::new(BinaryOperator { kind: Mul, lhs: Box::new((*lhs).clone()), rhs: Box::new(Node::dif((*rhs).clone(), node2.clone())) })
-                        }),
-                        rhs: Box::new(BinaryOperator { kind: Pow, lhs: Box::new(node2.clone()), rhs: Box::new(Num { val: 2.0 }) })
-                    },
-                    Pow => BinaryOperator {
-                        kind: Add,
-                        lhs: Box::new(BinaryOperator {
-                            kind: Mul,
-                            lhs: Box::new(BinaryOperator {
-                                kind: Mul,
-                                lhs: Box::new((*rhs).clone()),
-                                rhs: Box::new(BinaryOperator {
-                                    kind: Pow,
-                                    lhs: Box::new((*lhs).clone()),
-                                    rhs: Box::new(BinaryOperator {
-                                        kind: Sub,
-                                        lhs: Box::new((*rhs).clone()),
-                                        rhs: Box::new(Num { val: 1.0 })
-                                    })
-                                })
-                            }),
-                            rhs: Box::new(Node::dif((*lhs).clone(), node2.clone()))
-                        }),
-                        rhs: Box::new(BinaryOperator {
-                            kind: Mul,
-                            lhs: Box::new(BinaryOperator {
-                                kind: Mul,
-                                lhs: Box::new(BinaryOperator {
-                                    kind: Pow,
-                                    lhs: Box::new((*lhs).clone()),
-                                    rhs: Box::new((*rhs).clone())
-                                }),
-                                rhs: Box::new(UnaryOperator {
-                                    kind: Log,
-                                    operand: Box::new((*lhs).clone())
-                                })
-                            }),
-                            rhs: Box::new(Node::dif((*rhs).clone(), node2.clone()))
-                        })
-                    },
+                    Add => Node::binary(Add, Node::dif(lhs, node2), Node::dif(rhs, node2)),
+                    Sub => Node::binary(Sub, Node::dif(lhs, node2), Node::dif(rhs, node2)),
+                    Mul => Node::binary(
+                        Add,
+                        Node::binary(Mul, Node::dif(lhs, node2), rhs.clone()),
+                        Node::binary(Mul, lhs.clone(), Node::dif(rhs, node2))
+                    ),
+                    Div => Node::binary(
+                        Div,
+                        Node::binary(
+                            Sub,
+                            Node::binary(Mul, Node::dif(lhs, node2), rhs.clone()),
+                            Node::binary(Mul, lhs.clone(), Node::dif(rhs, node2))
+                        ),
+                        Node::binary(Pow, rhs.clone(), Node::num(2.0))
+                    ),
+                    Pow => Node::binary(
+                        Add,
+                        Node::binary(
+                            Mul,
+                            Node::binary(
+                                Mul,
+                                rhs.clone(),
+                                Node::binary(Pow, lhs.clone(), Node::binary(Sub, rhs.clone(), Node::num(1.0)))
+                            ),
+                            Node::dif(lhs, node2)
+                        ),
+                        Node::binary(
+                            Mul,
+                            Node::binary(Mul, node1.clone(), Node::unary(Log, lhs.clone())),
+                            Node::dif(rhs, node2)
+                        )
+                    ),
                 }
             },
             UnaryOperator { kind, operand } => {
                 match kind {
-                    Neg => UnaryOperator { kind: Neg, operand: Box::new(Node::dif(*operand, node2)) },
-                    Sin => BinaryOperator {
-                        kind: Mul,
-                        lhs: Box::new(Node::dif((*operand).clone(), node2)),
-                        rhs: Box::new(UnaryOperator { kind: Cos, operand: Box::new((*operand).clone()) })
-                    },
-                    Cos => UnaryOperator {
-                        kind: Neg,
-                        operand: Box::new(BinaryOperator {
-                            kind: Mul,
-                            lhs: Box::new(Node::dif((*operand).clone(), node2)),
-                            rhs: Box::new(UnaryOperator { kind: Sin, operand: Box::new((*operand).clone()) })
-                        })
-                    },
-                    Tan => BinaryOperator {
-                        kind: Div,
-                        lhs: Box::new(Node::dif((*operand).clone(), node2.clone())),
-                        rhs: Box::new(BinaryOperator {
-                            kind: Pow,
-                            lhs: Box::new(UnaryOperator { kind: Cos, operand: Box::new((*operand).clone()) }),
-                            rhs: Box::new(Num { val: 2.0 })
-                        })
-                    },
-                    Exp => BinaryOperator {
-                        kind: Mul,
-                        lhs: Box::new(Node::dif((*operand).clone(), node2.clone())),
-                        rhs: Box::new(UnaryOperator { kind: Exp, operand: Box::new((*operand).clone()) })
-                    },
-                    Log => BinaryOperator {
-                        kind: Div,
-                        lhs: Box::new(Node::dif((*operand).clone(), node2)),
-                        rhs: Box::new((*operand).clone())
-                    },
+                    Neg => Node::unary(Neg, Node::dif(operand, node2)),
+                    Sin => Node::binary(Mul, Node::dif(operand, node2), Node::unary(Cos, operand.clone())),
+                    Cos => Node::unary(
+                        Neg,
+                        Node::binary(Mul, Node::dif(operand, node2), Node::unary(Sin, operand.clone()))
+                    ),
+                    Tan => Node::binary(
+                        Div,
+                        Node::dif(operand, node2),
+                        Node::binary(Pow, Node::unary(Cos, operand.clone()), Node::num(2.0))
+                    ),
+                    Exp => Node::binary(Mul, Node::dif(operand, node2), node1.clone()),
+                    Log => Node::binary(Div, Node::dif(operand, node2), operand.clone()),
+                    Asin => Node::binary(
+                        Div,
+                        Node::dif(operand, node2),
+                        Node::binary(
+                            Pow,
+                            Node::binary(Sub, Node::num(1.0), Node::binary(Pow, operand.clone(), Node::num(2.0))),
+                            Node::num(0.5)
+                        )
+                    ),
+                    Re | Im | Conj => Node::unary(*kind, Node::dif(operand, node2)),
+                    // `d|u| = re(conj(u) du) / |u|` and `d arg(u) = im(du / u)`.
+                    Abs => Node::binary(
+                        Div,
+                        Node::unary(Re, Node::binary(Mul, Node::unary(Conj, operand.clone()), Node::dif(operand, node2))),
+                        node1.clone()
+                    ),
+                    Arg => Node::unary(Im, Node::binary(Div, Node::dif(operand, node2), operand.clone())),
                 }
             },
-            Var { name, point } => {
-                match node2 {
-                    Var { name: name_, .. } if name == name_ => {
-                        return Num { val: 1.0 };
-                    },
+            Var { name, point, .. } => {
+                match &**node2 {
+                    Var { name: name_, .. } if name == name_ => Node::num(1.0),
+                    Var { .. } if Node::depends_on(node1, node2) => Node::derivative(node1.clone(), node2.clone()),
                     Var { .. } => {
                         match point {
-                            Some(node) => Node::dif(*node, node2.clone()),
-                            None => Num { val: 0.0 }
+                            Some(node) => Node::dif(node, node2),
+                            None => Node::num(0.0),
                         }
                     },
-                    _ => {
-                        Num { val: 0.0 }
-                    },
+                    _ => Node::num(0.0),
                 }
             },
-            Num { .. } => Num { val: 0.0 },
+            // Derivatives are independent of each other and of the function, as
+            // the generalised coordinates and velocities of mechanics are.
+            Derivative { .. } if Node::equiv(node1, node2) => Node::num(1.0),
+            Derivative { .. } if Node::depends_on(node1, node2) => Node::derivative(node1.clone(), node2.clone()),
+            Num { .. } | Const { .. } | Derivative { .. } => Node::num(0.0),
+            List { items } => Node::list(items.iter().map(|item| Node::dif(item, node2)).collect()),
+            Equation { lhs, rhs } => Node::equation(Node::dif(lhs, node2), Node::dif(rhs, node2)),
+            // The implicit function theorem: the root `r(p)` of `F(x, p) = 0`
+            // moves by `-F_p / F_x`, both taken at `x = r(p)`.
+            Unsolved { equation, var } => {
+                if matches!((&**var, &**node2), (Var { name, .. }, Var { name: name_, .. }) if name == name_) {
+                    return Node::num(0.0);
+                }
+                let f = match &**equation {
+                    Equation { lhs, rhs } => Node::binary(Sub, lhs.clone(), rhs.clone()),
+                    _ => equation.clone(),
+                };
+                let at_root = |node: Rc<Node>| Node::substitute(&node, var, node1);
+                Node::unary(
+                    Neg,
+                    Node::binary(Div, at_root(Node::dif(&f, node2)), at_root(Node::dif(&f, var)))
+                )
+            },
+        }
+    }
+
+    // Replaces every variable that has a defining expression by that expression.
+    pub fn resolve(node: &Rc<Node>) -> Rc<Node> {
+        Node::resolve_shared(node, &mut HashMap::new())
+    }
+
+    fn resolve_shared(node: &Rc<Node>, memo: &mut HashMap<*const Node, Rc<Node>>) -> Rc<Node> {
+        if let Some(res) = memo.get(&Rc::as_ptr(node)) {
+            return res.clone();
+        }
+        let res = match &**node {
+            BinaryOperator { kind, lhs, rhs } => {
+                Node::binary(*kind, Node::resolve_shared(lhs, memo), Node::resolve_shared(rhs, memo))
+            },
+            UnaryOperator { kind, operand } => Node::unary(*kind, Node::resolve_shared(operand, memo)),
+            Var { point: Some(point), .. } => Node::resolve_shared(point, memo),
+            Var { point: None, .. } | Num { .. } | Const { .. } | Derivative { .. } => node.clone(),
+            List { items } => Node::list(items.iter().map(|item| Node::resolve_shared(item, memo)).collect()),
+            Equation { lhs, rhs } => Node::equation(Node::resolve_shared(lhs, memo), Node::resolve_shared(rhs, memo)),
+            Unsolved { equation, var } => Node::unsolved(Node::resolve_shared(equation, memo), var.clone()),
+        };
+        memo.insert(Rc::as_ptr(node), res.clone());
+        res
+    }
+
+    // Replaces the free variable `var` by `value`, after resolving definitions.
+    pub fn substitute(node: &Rc<Node>, var: &Rc<Node>, value: &Rc<Node>) -> Rc<Node> {
+        fn visit(node: &Rc<Node>, var: &Rc<Node>, value: &Rc<Node>, memo: &mut HashMap<*const Node, Rc<Node>>) -> Rc<Node> {
+            if Node::equiv(node, var) {
+                return value.clone();
+            }
+            if let Some(res) = memo.get(&Rc::as_ptr(node)) {
+                return res.clone();
+            }
+            let res = match &**node {
+                BinaryOperator { kind, lhs, rhs } => {
+                    Node::binary(*kind, visit(lhs, var, value, memo), visit(rhs, var, value, memo))
+                },
+                UnaryOperator { kind, operand } => Node::unary(*kind, visit(operand, var, value, memo)),
+                List { items } => Node::list(items.iter().map(|item| visit(item, var, value, memo)).collect()),
+                Equation { lhs, rhs } => Node::equation(visit(lhs, var, value, memo), visit(rhs, var, value, memo)),
+                // The variable solved for is bound, so only other variables are replaced.
+                Unsolved { equation, var: bound } if Node::equiv(bound, var) => Node::unsolved(equation.clone(), bound.clone()),
+                Unsolved { equation, var: bound } => Node::unsolved(visit(equation, var, value, memo), bound.clone()),
+                Var { .. } | Num { .. } | Const { .. } | Derivative { .. } => node.clone(),
+            };
+            memo.insert(Rc::as_ptr(node), res.clone());
+            res
+        }
+        visit(&Node::resolve(node), var, value, &mut HashMap::new())
+    }
+
+    // Names of the variables `node` depends on once defining expressions are
+    // followed, in order of first appearance.
+    pub fn free_vars(node: &Rc<Node>) -> Vec<String> {
+        fn visit(node: &Rc<Node>, seen: &mut HashSet<*const Node>, names: &mut Vec<String>) {
+            if !seen.insert(Rc::as_ptr(node)) {
+                return;
+            }
+            match &**node {
+                // The variable solved for is bound by `solve`.
+                Unsolved { equation, var } => {
+                    let bound = Node::free_vars(var);
+                    for name in Node::free_vars(equation) {
+                        if !bound.contains(&name) && !names.contains(&name) {
+                            names.push(name);
+                        }
+                    }
+                },
+                BinaryOperator { lhs, rhs, .. } | Equation { lhs, rhs } => {
+                    visit(lhs, seen, names);
+                    visit(rhs, seen, names);
+                },
+                UnaryOperator { operand, .. } => visit(operand, seen, names),
+                Var { point: Some(point), .. } => visit(point, seen, names),
+                Var { name, point: None, .. } => {
+                    if !names.contains(name) {
+                        names.push(name.clone());
+                    }
+                },
+                // Named as printed, which is how values are bound to it.
+                Derivative { .. } => {
+                    let name = node.to_string();
+                    if !names.contains(&name) {
+                        names.push(name);
+                    }
+                },
+                Num { .. } | Const { .. } => {},
+                List { items } => {
+                    for item in items {
+                        visit(item, seen, names);
+                    }
+                },
+            }
         }
+        let mut names = Vec::new();
+        visit(node, &mut HashSet::new(), &mut names);
+        names
     }
 
-    pub fn print(&self, indent: usize) {
+    // Binding strength of the grammar rule that parses this node: 0 for `expr`,
+    // 1 for `add`, 2 for `mul`, 3 for `power`, 4 for `unary` and 5 for `prim`.
+    fn level(&self) -> usize {
+        match self {
+            Equation { .. } => 0,
+            BinaryOperator { kind: Add | Sub, .. } => 1,
+            BinaryOperator { kind: Mul | Div, .. } => 2,
+            BinaryOperator { kind: Pow, .. } => 3,
+            UnaryOperator { .. } => 4,
+            Var { point: Some(point), .. } => point.level(),
+            Var { point: None, .. } => 5,
+            Num { val } if val.is_sign_negative() => 4,
+            Num { .. } => 5,
+            Const { .. } | List { .. } | Derivative { .. } | Unsolved { .. } => 5,
+        }
+    }
+
+    fn fmt_level(&self, f: &mut fmt::Formatter, level: usize) -> fmt::Result {
+        if self.level() < level {
+            write!(f, "(")?;
+            self.fmt_level(f, 0)?;
+            return write!(f, ")");
+        }
         match self {
             BinaryOperator { kind, lhs, rhs } => {
-                lhs.print(indent + 1);
-                match kind {
-                    Add => { print!(" + "); }
-                    Sub => { print!(" - "); }
-                    Mul => { print!(" * "); }
-                    Div => { print!(" / "); }
-                    Pow => { print!(" ^ "); }
-                }
-                rhs.print(indent + 1);
+                let (symbol, lhs_level, rhs_level) = match kind {
+                    Add => ("+", 1, 2),
+                    Sub => ("-", 1, 2),
+                    Mul => ("*", 2, 3),
+                    Div => ("/", 2, 3),
+                    Pow => ("^", 4, 3),
+                };
+                lhs.fmt_level(f, lhs_level)?;
+                write!(f, " {} ", symbol)?;
+                rhs.fmt_level(f, rhs_level)
+            },
+            UnaryOperator { kind: Neg, operand } => {
+                write!(f, "-")?;
+                operand.fmt_level(f, 4)
             },
             UnaryOperator { kind, operand } => {
-                match kind {
-                    Neg => { print!("- "); }
-                    Sin => { print!("sin "); }
-                    Cos => { print!("cos "); }
-                    Tan => { print!("tan "); }
-                    Exp => { print!("exp "); }
-                    Log => { print!("log "); }
-                }
-                operand.print(indent + 1);
+                let name = match kind {
+                    Neg => "-",
+                    Sin => "sin",
+                    Cos => "cos",
+                    Tan => "tan",
+                    Exp => "exp",
+                    Log => "log",
+                    Asin => "asin",
+                    Re => "re",
+                    Im => "im",
+                    Conj => "conj",
+                    Arg => "arg",
+                    Abs => "abs",
+                };
+                write!(f, "{}(", name)?;
+                operand.fmt_level(f, 0)?;
+                write!(f, ")")
+            },
+            Derivative { of, var } => {
+                write!(f, "dif(")?;
+                of.fmt_level(f, 0)?;
+                write!(f, ", ")?;
+                var.fmt_level(f, 0)?;
+                write!(f, ")")
+            },
+            Equation { lhs, rhs } => {
+                lhs.fmt_level(f, 1)?;
+                write!(f, " == ")?;
+                rhs.fmt_level(f, 1)
             },
-            Var { name, point } => {
+            Unsolved { equation, var } => {
+                write!(f, "solve(")?;
+                equation.fmt_level(f, 0)?;
+                write!(f, ", ")?;
+                var.fmt_level(f, 0)?;
+                write!(f, ")")
+            },
+            Var { name, point, .. } => {
                 match point {
-                    Some(node) => {
-                        node.print(indent + 1);
-                    },
-                    None => {
-                        print!("{}", name);
-                    },
+                    Some(node) => node.fmt_level(f, level),
+                    None => write!(f, "{}", name),
                 }
             },
             Num { val } => {
-                print!("{}", val);
+                write!(f, "{}", val)
+            },
+            Const { kind } => {
+                write!(f, "{}", kind.name())
+            },
+            List { items } => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    item.fmt_level(f, 0)?;
+                }
+                write!(f, "]")
             },
         }
-        if indent == 0 {
-            println!();
+    }
+
+    // Matrices print one row per line, with their columns aligned.
+    pub fn print(&self) {
+        let rows = match self {
+            Var { point: Some(point), .. } => return point.print(),
+            List { items } if !items.is_empty() => items.iter().map(|item| match &**item {
+                List { items } => Some(items.iter().map(|item| item.to_string()).collect::<Vec<String>>()),
+                _ => None,
+            }).collect::<Option<Vec<Vec<String>>>>(),
+            _ => None,
+        };
+        let Some(rows) = rows.filter(|rows| rows.iter().all(|row| row.len() == rows[0].len() && !row.is_empty())) else {
+            println!("{}", self);
+            return;
+        };
+        let widths = (0..rows[0].len()).map(|j| rows.iter().map(|row| row[j].chars().count()).max().unwrap()).collect::<Vec<usize>>();
+        for (i, row) in rows.iter().enumerate() {
+            let cells = row.iter().zip(&widths).enumerate().map(|(j, (cell, width))| {
+                if j + 1 < row.len() { format!("{:<width$}", format!("{},", cell), width = width + 1) } else { cell.clone() }
+            }).collect::<Vec<String>>();
+            let open = if i == 0 { "[[" } else { " [" };
+            let close = if i + 1 == rows.len() { "]]" } else { "]," };
+            println!("{}{}{}", open, cells.join(" "), close);
         }
     }
+}
+
+// Prints valid SigMa source, with only the parentheses the grammar requires.
+impl fmt::Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_level(f, 0)
+    }
 }
\ No newline at end of file
@@ -1,42 +1,105 @@
+use std::collections::HashMap;
+use std::fs;
+use std::rc::Rc;
+
 use crate::node::*;
 use crate::lexer::*;
+use crate::eval::*;
+use crate::reverse;
+use crate::dual;
+use crate::complex;
+use crate::cse;
+use crate::codegen::{self, Lang};
+use crate::bytecode::Program;
+use crate::table;
+use crate::plot;
+use crate::root;
+use crate::solve;
+use crate::poly;
+use crate::factor;
+use crate::apart;
+use crate::limit;
+use crate::matrix;
+use crate::implicit;
+use crate::lagrange;
+use crate::vector::{self, Coords};
+use crate::simplify::simplify;
 
 use Token::*;
 use BKind::*;
 use UKind::*;
 use Node::*;
 
+fn report<T>(res: Result<T, String>) -> Result<T,()> {
+    res.map_err(|msg| println!("\x1b[31merror\x1b[39m: {}", msg))
+}
+
+// Evaluates expressions that may not depend on any unbound variable, such as the
+// coordinates of a point.
+fn numeric(nodes: &[Rc<Node>]) -> Result<Vec<f64>, String> {
+    nodes.iter().map(|node| eval(node, &Env::new())).collect()
+}
+
+fn var_names(vars: &[Rc<Node>]) -> Result<Vec<String>, String> {
+    vars.iter().map(|var| match &**var {
+        Node::Var { name, .. } => Ok(name.clone()),
+        _ => Err("expected a variable".to_string()),
+    }).collect()
+}
+
+// Writes `text` to the file at `path`, or to standard output without one.
+fn write(path: Option<String>, text: &str) -> Result<(), String> {
+    match path {
+        Some(path) => fs::write(&path, text).map_err(|err| format!("cannot write {}: {}", path, err)),
+        None => {
+            print!("{}", text);
+            Ok(())
+        },
+    }
+}
+
 #[derive(Debug, Clone)]
 struct Operator {
     name: String,
-    args: Vec<Node>,
-    cont: Node,
+    args: Vec<Rc<Node>>,
+    cont: Rc<Node>,
 }
 
 impl Operator {
-    fn construct(&self, cont: Node, params: Vec<Node>) -> Node {
-        match cont {
+    // Rebuilds the body with the arguments in place. Lists passed as arguments
+    // go through the same list arithmetic as when they are written out.
+    fn construct(&self, cont: &Rc<Node>, params: &[Rc<Node>], memo: &mut HashMap<*const Node, Rc<Node>>) -> Result<Rc<Node>, String> {
+        if let Some(node) = memo.get(&Rc::as_ptr(cont)) {
+            return Ok(node.clone());
+        }
+        let node = match &**cont {
             BinaryOperator { kind, lhs, rhs } => {
-                BinaryOperator { kind, lhs: Box::new(self.construct(*lhs, params.clone())), rhs: Box::new(self.construct(*rhs, params.clone())) }
+                matrix::binary(*kind, self.construct(lhs, params, memo)?, self.construct(rhs, params, memo)?)?
             },
             UnaryOperator { kind, operand } => {
-                UnaryOperator { kind, operand: Box::new(self.construct(*operand, params.clone())) }
+                matrix::unary(*kind, self.construct(operand, params, memo)?)
             },
-            Var { name, point } => {
-                for i in 0..self.args.len() {
-                    match self.args[i] {
-                        Var { name: ref name_, .. } if *name_ == name => {
-                            return params[i].clone();
-                        },
-                        _ => {}
-                    }
+            Var { .. } => {
+                match self.args.iter().position(|arg| Node::equiv(arg, cont)) {
+                    Some(i) => params[i].clone(),
+                    None => cont.clone(),
                 }
-                Var { name, point }
             },
-            Node::Num { .. } => {
-                cont
+            Node::Num { .. } | Const { .. } | Derivative { .. } => {
+                cont.clone()
             },
-        }
+            List { items } => {
+                Node::list(items.iter().map(|item| self.construct(item, params, memo)).collect::<Result<_, _>>()?)
+            },
+            Equation { lhs, rhs } => {
+                matrix::equation(self.construct(lhs, params, memo)?, self.construct(rhs, params, memo)?)?
+            },
+            Unsolved { equation, var } => {
+                Node::unsolved(self.construct(equation, params, memo)?, self.construct(var, params, memo)?)
+            },
+        };
+        memo.insert(Rc::as_ptr(cont), node.clone());
+        Ok(node)
     }
 }
 
@@ -54,10 +117,6 @@ impl OperatorTable {
         self.vec.push(item);
     }
 
-    fn pop(&mut self) {
-        self.vec.pop();
-    }
-
     fn find(&mut self, name: String) -> Option<Operator> {
         for i in (0..self.vec.len()).rev() {
             match self.vec[i].clone() {
@@ -73,7 +132,7 @@ impl OperatorTable {
 
 #[derive(Debug, Clone)]
 struct SymbolTable {
-    vec: Vec<Node>,
+    vec: Vec<Rc<Node>>,
 }
 
 impl SymbolTable {
@@ -81,25 +140,18 @@ impl SymbolTable {
         SymbolTable { vec: Vec::new() }
     }
 
-    fn push(&mut self, node: Node) {
+    fn push(&mut self, node: Rc<Node>) {
         self.vec.push(node);
-        // match node {
-        //     Node::Var { name: ref name, point: ref point } => {
-        //         self.vec.push(node);
-        //     },
-        //     _ => {
-        //     },
-        // }
     }
 
     fn pop(&mut self) {
         self.vec.pop();
     }
 
-    fn find(&mut self, name: String) -> Option<Node> {
+    fn find(&mut self, name: String) -> Option<Rc<Node>> {
         for i in (0..self.vec.len()).rev() {
-            match self.vec[i].clone() {
-                Node::Var { name: name_, .. } if name_ == name => {
+            match &*self.vec[i] {
+                Node::Var { name: name_, .. } if *name_ == name => {
                     return Some(self.vec[i].clone());
                 },
                 _ => {},
@@ -108,11 +160,11 @@ impl SymbolTable {
         None
     }
 
-    fn set(&mut self, name: String, node: Node) {
+    fn set(&mut self, name: String, node: Rc<Node>) {
         for i in (0..self.vec.len()).rev() {
-            match self.vec[i].clone() {
-                Node::Var { name: name_, .. } if name_ == name => {
-                    self.vec[i] = Node::Var { name: name, point: Some(Box::new(node)) };
+            match &*self.vec[i] {
+                Node::Var { name: name_, .. } if *name_ == name => {
+                    self.vec[i] = Node::var(&name, Some(node));
                     return;
                 },
                 _ => {},
@@ -122,46 +174,77 @@ impl SymbolTable {
     }
 }
 
+// Builtin functions. Their names are identifiers rather than keywords, so that
+// programs written before a builtin was added may still use its name; a name
+// calls the builtin only when followed by `(` and not declared by the program.
+const BUILTINS: [&str; 29] = [
+    "asin", "re", "im", "conj", "arg", "abs", "solve", "expand", "collect", "degree", "coeff", "gcd", "sqf", "factor",
+    "cancel", "simplify", "apart", "limit", "transpose", "det", "inv", "trace", "grad", "divergence", "curl",
+    "laplacian", "directional", "implicit_dif", "euler_lagrange",
+];
+
 pub struct Parser {
     token_list: Vec<Token>,
     pos: usize,
     symbol_table: SymbolTable,
     op_table: OperatorTable,
+    print_mode: Option<String>,
+    // Inside an operator body, where arguments may turn out to be lists, list
+    // arithmetic waits until the operator is applied.
+    in_op: bool,
 }
 
 impl Parser {
-    pub fn new<'a>(token_list: &'a Vec<Token>) -> Self {
+    pub fn new(token_list: &[Token]) -> Self {
         Parser {
-            token_list: token_list.clone(),
+            token_list: token_list.to_vec(),
             pos: 0,
             symbol_table: SymbolTable::new(),
             op_table: OperatorTable::new(),
+            print_mode: None,
+            in_op: false,
         }
     }
 
-    pub fn prog(&mut self) -> Result<Vec<Node>,()> {
-        let mut node_list: Vec<Node> = Vec::new();
+    // Makes `print` without an explicit `print:mode` use `mode`.
+    pub fn set_print_mode(&mut self, mode: &str) {
+        self.print_mode = Some(mode.to_string());
+    }
+
+    pub fn prog(&mut self) -> Result<(),()> {
         while self.pos < self.token_list.len() {
             self.stmt()?;
         }
-        // println!("{:?}", self.symbol_table);
-        Ok(node_list)
+        Ok(())
     }
 
     fn stmt(&mut self) -> Result<(),()> {
         let token = self.token_list[self.pos].clone();
         match token {
-            Token::Reserved(s) if s == "var" => {
+            Token::Reserved(s) if s == "var" || s == "let" => {
                 self.inc();
 
                 loop {
-                    let var: Node;
                     let name = self.next_ident()?;
-                    if self.expect("=") {
-                        var = Node::Var { name: name, point: Some(Box::new(self.expr()?)) };
+                    let var = if s == "var" && self.expect("(") {
+                        // `var y(t)`: an unknown function of `t`.
+                        let mut deps = Vec::new();
+                        loop {
+                            deps.push(self.next_ident()?);
+                            if self.expect(",") { continue; }
+                            if self.expect(")") { break; }
+                            println!("\x1b[31merror\x1b[39m: expected ')'");
+                            return Err(());
+                        }
+                        Node::dependent(&name, deps)
+                    } else if self.expect("=") {
+                        Node::var(&name, Some(self.expr()?))
+                    } else if s == "let" {
+                        println!("\x1b[31merror\x1b[39m: expected '='");
+                        return Err(());
                     } else {
-                        var = Node::Var { name: name, point: None };
-                    }
+                        Node::var(&name, None)
+                    };
                     self.symbol_table.push(var);
                     
                     if self.expect(",") { continue; }
@@ -176,7 +259,7 @@ impl Parser {
 
                 self.consume("(");
                 loop {
-                    let arg = Node::Var { name: self.next_ident()?, point: None };
+                    let arg = Node::var(&self.next_ident()?, None);
                     args.push(arg.clone());
                     self.symbol_table.push(arg);
                     if self.expect(",") { continue; }
@@ -184,8 +267,14 @@ impl Parser {
                 }
 
                 self.consume("{");
-                let cont = self.expr()?;
+                self.in_op = true;
+                let cont = self.expr();
+                self.in_op = false;
+                let cont = cont?;
                 self.consume("}");
+                for _ in 0..args.len() {
+                    self.symbol_table.pop();
+                }
 
                 let operator = Operator { name, args, cont };
                 self.op_table.push(operator);
@@ -193,15 +282,294 @@ impl Parser {
             Token::Reserved(s) if s == "print" => {
                 self.inc();
 
+                let mode = if self.expect(":") { Some(self.next_ident()?) } else { self.print_mode.clone() };
+                let node = self.expr()?;
+                match mode.as_deref() {
+                    None => node.print(),
+                    Some("numpy") => {
+                        print!("{}", report(codegen::function(Lang::NumPy, "f", &Node::free_vars(&node), &node, false))?);
+                    },
+                    Some("sympy") => {
+                        println!("{}", codegen::expression(Lang::SymPy, &node));
+                    },
+                    Some("cse") => {
+                        let cse = cse::eliminate(&[node]);
+                        for (name, temp) in &cse.temps {
+                            println!("let {} = {};", name, temp);
+                        }
+                        println!("{};", cse.roots[0]);
+                    },
+                    Some(mode) => {
+                        println!("\x1b[31merror\x1b[39m: unknown print mode '{}'", mode);
+                        return Err(());
+                    },
+                }
+            },
+            Token::Reserved(s) if s == "gradat" => {
+                self.inc();
+
+                self.consume("(");
+                let node = self.expr()?;
+                self.consume(",");
+                let vars = self.list()?;
+                self.consume(",");
+                let point = self.list()?;
+                self.consume(")");
+
+                if vars.len() != point.len() {
+                    println!("\x1b[31merror\x1b[39m: expected {} coordinates, found {}", vars.len(), point.len());
+                    return Err(());
+                }
+                let point = report(numeric(&point))?;
+                let (val, grad) = report(reverse::gradient(&node, &vars, &point))?;
+                let grad = grad.iter().map(|partial| partial.to_string()).collect::<Vec<String>>();
+                println!("value: {}", val);
+                println!("gradient: [{}]", grad.join(", "));
+            },
+            Token::Reserved(s) if s == "evalat" => {
+                self.inc();
+
+                self.consume("(");
+                let node = self.expr()?;
+                let (vars, point) = if self.expect(",") {
+                    let vars = self.list()?;
+                    self.consume(",");
+                    (vars, self.list()?)
+                } else {
+                    (Vec::new(), Vec::new())
+                };
+                self.consume(")");
+
+                if vars.len() != point.len() {
+                    println!("\x1b[31merror\x1b[39m: expected {} coordinates, found {}", vars.len(), point.len());
+                    return Err(());
+                }
+                println!("value: {}", report(complex::value(&node, &vars, &point))?);
+            },
+            Token::Reserved(s) if s == "difat" || s == "difat2" => {
+                self.inc();
+
+                self.consume("(");
+                let node = self.expr()?;
+                self.consume(",");
+                let (vars, point, direction) = if self.peek("[") {
+                    let vars = self.list()?;
+                    self.consume(",");
+                    let point = self.list()?;
+                    self.consume(",");
+                    let direction = self.list()?;
+                    (vars, point, direction)
+                } else {
+                    let var = self.expr()?;
+                    self.consume(",");
+                    let point = self.expr()?;
+                    (vec![var], vec![point], vec![Node::num(1.0)])
+                };
+                self.consume(")");
+
+                if vars.len() != point.len() || vars.len() != direction.len() {
+                    println!("\x1b[31merror\x1b[39m: expected {} coordinates", vars.len());
+                    return Err(());
+                }
+                let point = report(numeric(&point))?;
+                let direction = report(numeric(&direction))?;
+                if s == "difat" {
+                    let (val, first) = report(dual::derivative(&node, &vars, &point, &direction))?;
+                    println!("value: {}", val);
+                    println!("derivative: {}", first);
+                } else {
+                    let (val, first, second) = report(dual::second_derivative(&node, &vars, &point, &direction))?;
+                    println!("value: {}", val);
+                    println!("derivative: {}", first);
+                    println!("second derivative: {}", second);
+                }
+            },
+            Token::Reserved(s) if s == "solve_numeric" => {
+                self.inc();
+
+                self.consume("(");
                 let node = self.expr()?;
-                node.print(0);
+                self.consume(",");
+                let var = self.expr()?;
+                self.consume(",");
+                let (guess, bracket) = if self.peek("[") {
+                    (None, Some(self.list()?))
+                } else {
+                    let guess = self.expr()?;
+                    let bracket = if self.expect(",") { Some(self.list()?) } else { None };
+                    (Some(guess), bracket)
+                };
+                self.consume(")");
+                let tolerance = if self.expect_ident("tolerance") { Some(self.expr()?) } else { None };
+                let iterations = if self.expect_ident("iterations") { Some(self.expr()?) } else { None };
+
+                let names = report(var_names(std::slice::from_ref(&var)))?;
+                let bracket = match bracket {
+                    Some(bracket) if bracket.len() == 2 => {
+                        let ends = report(numeric(&bracket))?;
+                        if ends[0] >= ends[1] {
+                            println!("\x1b[31merror\x1b[39m: empty bracket [{}, {}]", ends[0], ends[1]);
+                            return Err(());
+                        }
+                        Some((ends[0], ends[1]))
+                    },
+                    Some(bracket) => {
+                        println!("\x1b[31merror\x1b[39m: a bracket has 2 ends, found {}", bracket.len());
+                        return Err(());
+                    },
+                    None => None,
+                };
+                let guess = match guess {
+                    Some(guess) => report(eval::<f64>(&guess, &Env::new()))?,
+                    None => bracket.map(|(a, b)| 0.5 * (a + b)).unwrap(),
+                };
+                let tolerance = match tolerance {
+                    Some(tolerance) => report(eval::<f64>(&tolerance, &Env::new()))?,
+                    None => 1e-12,
+                };
+                let iterations = match iterations {
+                    Some(iterations) => {
+                        let count = report(eval::<f64>(&iterations, &Env::new()))?;
+                        if !(count >= 1.0 && count.fract() == 0.0 && count <= u32::MAX as f64) {
+                            println!("\x1b[31merror\x1b[39m: iterations must be a positive integer, found {}", count);
+                            return Err(());
+                        }
+                        count as usize
+                    },
+                    None => 100,
+                };
+
+                let f = report(Program::compile(&node, &names))?;
+                let df = report(Program::compile(&Node::dif(&node, &var), &names))?;
+                let (method, res) = match (root::newton(&f, &df, guess, bracket, tolerance, iterations), bracket) {
+                    (Ok(res), _) => ("newton", res),
+                    (Err(msg), Some(bracket)) => {
+                        println!("note: newton's method failed: {}; falling back to brent", msg);
+                        ("brent", report(root::brent(&f, bracket, tolerance, iterations))?)
+                    },
+                    (Err(msg), None) => {
+                        println!("\x1b[31merror\x1b[39m: newton's method failed: {}", msg);
+                        return Err(());
+                    },
+                };
+                println!("root: {}", res.x);
+                println!("residual: {}", res.residual);
+                println!("iterations: {} ({})", res.iterations, method);
+            },
+            Token::Reserved(s) if s == "table" => {
+                self.inc();
+
+                let csv = if self.expect(":") {
+                    match self.next_ident()?.as_str() {
+                        "csv" => true,
+                        "text" => false,
+                        mode => {
+                            println!("\x1b[31merror\x1b[39m: unknown table mode '{}'", mode);
+                            return Err(());
+                        },
+                    }
+                } else {
+                    false
+                };
+                let nodes = self.exprs()?;
+                let (var, from, to, step) = self.range()?;
+                let path = self.destination()?;
+
+                let xs = report(table::grid(from, to, step.unwrap_or(1.0)))?;
+                let mut headers = vec![var.clone()];
+                let mut columns = vec![xs.clone()];
+                for (node, label) in nodes {
+                    let program = report(Program::compile(&node, std::slice::from_ref(&var)))?;
+                    let mut ys = vec![0.0; xs.len()];
+                    program.run(&[&xs], &mut ys);
+                    headers.push(label);
+                    columns.push(ys);
+                }
+                report(write(path, &table::render(&headers, &columns, csv)))?;
+            },
+            Token::Reserved(s) if s == "plot" => {
+                self.inc();
+
+                let mode = if self.expect(":") { self.next_ident()? } else { "svg".to_string() };
+                if !["svg", "braille", "block"].contains(&mode.as_str()) {
+                    println!("\x1b[31merror\x1b[39m: unknown plot mode '{}'", mode);
+                    return Err(());
+                }
+                let nodes = if self.peek("[") {
+                    self.exprs()?
+                } else {
+                    let mut nodes = vec![self.labelled()?];
+                    while self.expect(",") {
+                        nodes.push(self.labelled()?);
+                    }
+                    nodes
+                };
+                let (var, from, to, _) = self.range()?;
+                let (width, height) = if self.expect_ident("size") {
+                    let width = self.expr()?;
+                    self.consume(",");
+                    let height = self.expr()?;
+                    (report(eval::<f64>(&width, &Env::new()))? as usize, report(eval::<f64>(&height, &Env::new()))? as usize)
+                } else {
+                    (72, 20)
+                };
+                let path = self.destination()?;
+
+                if width < 2 || height < 2 {
+                    println!("\x1b[31merror\x1b[39m: plot size must be at least 2 by 2");
+                    return Err(());
+                }
+                if from >= to {
+                    println!("\x1b[31merror\x1b[39m: empty range {}..{}", from, to);
+                    return Err(());
+                }
+                let mut curves = Vec::new();
+                for (node, label) in nodes {
+                    let program = report(Program::compile(&node, std::slice::from_ref(&var)))?;
+                    curves.push(plot::Curve { label, program });
+                }
+                let text = match mode.as_str() {
+                    "svg" => plot::svg(&curves, from, to),
+                    mode => plot::text(&curves, from, to, width, height, mode == "braille", path.is_none()),
+                };
+                report(write(path, &text))?;
             },
-            Token::Ident(name) => {
+            Token::Reserved(s) if s == "codegen" => {
+                self.inc();
+
+                let lang_name = self.next_ident()?;
+                let lang = match Lang::from_name(&lang_name) {
+                    Some(lang) => lang,
+                    None => {
+                        println!("\x1b[31merror\x1b[39m: unknown codegen target '{}'", lang_name);
+                        return Err(());
+                    },
+                };
+                let node = self.expr()?;
+                self.consume_ident("as")?;
+                let name = self.next_ident()?;
+                let mut params = Vec::new();
+                self.consume("(");
+                loop {
+                    params.push(self.next_ident()?);
+                    if self.expect(",") { continue; }
+                    if self.expect(")") { break; }
+                }
+                let gradient = if self.expect_ident("with") {
+                    self.consume_ident("gradient")?;
+                    true
+                } else {
+                    false
+                };
+
+                print!("{}", report(codegen::function(lang, &name, &params, &node, gradient))?);
+            },
+            Token::Ident(name) if !self.is_builtin(&name) => {
                 self.inc();
 
                 if self.expect("=") {
                     let value = self.expr()?;
-                    self.symbol_table.set(name.to_string(), Node::Var { name: name.to_string(), point: Some(Box::new(value)) });
+                    self.symbol_table.set(name, value);
                 }
             },
             _ => {
@@ -212,61 +580,108 @@ impl Parser {
         Ok(())
     }
 
-    fn expr(&mut self) -> Result<Node,()> {
-        self.add()
+    fn binary(&self, kind: BKind, lhs: Rc<Node>, rhs: Rc<Node>) -> Result<Rc<Node>,()> {
+        if self.in_op {
+            return Ok(Node::binary(kind, lhs, rhs));
+        }
+        report(matrix::binary(kind, lhs, rhs))
+    }
+
+    // `kind` applied to the operand that follows.
+    fn apply(&mut self, kind: UKind) -> Result<Rc<Node>,()> {
+        let operand = self.unary()?;
+        if self.in_op {
+            return Ok(Node::unary(kind, operand));
+        }
+        Ok(matrix::unary(kind, operand))
+    }
+
+    fn expr(&mut self) -> Result<Rc<Node>,()> {
+        let node = self.add()?;
+        if self.expect("==") {
+            let rhs = self.add()?;
+            if self.in_op {
+                return Ok(Node::equation(node, rhs));
+            }
+            return report(matrix::equation(node, rhs));
+        }
+        Ok(node)
     }
 
-    fn add(&mut self) -> Result<Node,()> {
+    fn add(&mut self) -> Result<Rc<Node>,()> {
         let mut node = self.mul()?;
         loop {
             if self.expect("+") {
-                node = BinaryOperator { kind: Add, lhs: Box::new(node), rhs: Box::new(self.mul()?) };
+                let rhs = self.mul()?;
+                node = self.binary(Add, node, rhs)?;
                 continue;
             }
             if self.expect("-") {
-                node = BinaryOperator { kind: Sub, lhs: Box::new(node), rhs: Box::new(self.mul()?) };
+                let rhs = self.mul()?;
+                node = self.binary(Sub, node, rhs)?;
                 continue;
             }
             return Ok(node);
         }
     }
 
-    fn mul(&mut self) -> Result<Node,()> {
+    fn mul(&mut self) -> Result<Rc<Node>,()> {
         let mut node = self.power()?;
         loop {
             if self.expect("*") {
-                node = BinaryOperator { kind: Mul, lhs: Box::new(node), rhs: Box::new(self.power()?) };
+                let rhs = self.power()?;
+                node = self.binary(Mul, node, rhs)?;
+                continue;
+            }
+            if self.expect(".*") {
+                // There is no node for it to wait in.
+                if self.in_op {
+                    println!("\x1b[31merror\x1b[39m: '.*' cannot be used in an operator body");
+                    return Err(());
+                }
+                node = report(matrix::elementwise(node, self.power()?))?;
                 continue;
             }
             if self.expect("/") {
-                node = BinaryOperator { kind: Div, lhs: Box::new(node), rhs: Box::new(self.power()?) };
+                let rhs = self.power()?;
+                node = self.binary(Div, node, rhs)?;
                 continue;
             }
             return Ok(node);
         }
     }
 
-    fn power(&mut self) -> Result<Node,()> {
+    fn power(&mut self) -> Result<Rc<Node>,()> {
         let mut node = self.unary()?;
         if self.expect("^") {
-            node = Node::BinaryOperator { kind: Pow, lhs: Box::new(node), rhs: Box::new(self.power()?) };
+            let rhs = self.power()?;
+            node = self.binary(Pow, node, rhs)?;
         }
         Ok(node)
     }
 
-    fn unary(&mut self) -> Result<Node,()> {
+    fn unary(&mut self) -> Result<Rc<Node>,()> {
         if self.expect("+") { return self.unary(); }
-        if self.expect("-") { return Ok(UnaryOperator { kind: Neg, operand: Box::new(self.unary()?) }); }
-        if self.expect("sin") { return Ok(UnaryOperator { kind: Sin, operand: Box::new(self.unary()?) }); }
-        if self.expect("cos") { return Ok(UnaryOperator { kind: Cos, operand: Box::new(self.unary()?) }); }
-        if self.expect("tan") { return Ok(UnaryOperator { kind: Tan, operand: Box::new(self.unary()?) }); }
-        if self.expect("exp") { return Ok(UnaryOperator { kind: Exp, operand: Box::new(self.unary()?) }); }
-        if self.expect("log") { return Ok(UnaryOperator { kind: Log, operand: Box::new(self.unary()?) }); }
+        if self.expect("-") { return self.apply(Neg); }
+        if self.expect("sin") { return self.apply(Sin); }
+        if self.expect("cos") { return self.apply(Cos); }
+        if self.expect("tan") { return self.apply(Tan); }
+        if self.expect("exp") { return self.apply(Exp); }
+        if self.expect("log") { return self.apply(Log); }
+        if self.expect_builtin("asin") { return self.apply(Asin); }
+        if self.expect_builtin("re") { return self.apply(Re); }
+        if self.expect_builtin("im") { return self.apply(Im); }
+        if self.expect_builtin("conj") { return self.apply(Conj); }
+        if self.expect_builtin("arg") { return self.apply(Arg); }
+        if self.expect_builtin("abs") { return self.apply(Abs); }
         self.prim()
     }
 
-    fn prim(&mut self) -> Result<Node,()> {
-        let token = self.token_list[self.pos].clone();
+    fn prim(&mut self) -> Result<Rc<Node>,()> {
+        let token = match self.token_list[self.pos].clone() {
+            Token::Ident(name) if self.is_builtin(&name) => Token::Reserved(name),
+            token => token,
+        };
         self.inc();
         match token {
             Token::Reserved(tok) if tok == "(" => {
@@ -281,7 +696,180 @@ impl Parser {
                 let rhs = self.expr()?;
                 self.consume(")");
                 
-                Ok(Node::dif(lhs.clone(), rhs.clone()))
+                Ok(matrix::dif(&lhs, &rhs))
+            },
+            Token::Reserved(tok) if tok == "solve" => {
+                self.consume("(");
+                let equation = self.expr()?;
+                self.consume(",");
+                let var = self.expr()?;
+                self.consume(")");
+
+                report(solve::solve(&equation, &var))
+            },
+            Token::Reserved(tok) if tok == "expand" => {
+                self.consume("(");
+                let node = self.expr()?;
+                self.consume(")");
+
+                report(poly::expand(&node))
+            },
+            Token::Reserved(tok) if tok == "collect" || tok == "degree" || tok == "apart" => {
+                self.consume("(");
+                let node = self.expr()?;
+                self.consume(",");
+                let var = self.expr()?;
+                self.consume(")");
+
+                match tok.as_str() {
+                    "collect" => report(poly::collect(&node, &var)),
+                    "degree" => report(poly::degree(&node, &var)),
+                    _ => report(apart::apart(&node, &var)),
+                }
+            },
+            Token::Reserved(tok) if tok == "coeff" => {
+                self.consume("(");
+                let node = self.expr()?;
+                self.consume(",");
+                let var = self.expr()?;
+                self.consume(",");
+                let n = self.expr()?;
+                self.consume(")");
+
+                report(poly::coeff(&node, &var, &n))
+            },
+            Token::Reserved(tok) if tok == "sqf" || tok == "factor" || tok == "cancel" || tok == "simplify" => {
+                self.consume("(");
+                let node = self.expr()?;
+                self.consume(")");
+
+                match tok.as_str() {
+                    "sqf" => report(factor::sqf(&node)),
+                    "factor" => report(factor::factor(&node)),
+                    "simplify" => Ok(simplify(&node)),
+                    _ => Ok(factor::cancel(&node)),
+                }
+            },
+            Token::Reserved(tok) if tok == "transpose" || tok == "det" || tok == "inv" || tok == "trace" => {
+                self.consume("(");
+                let node = self.expr()?;
+                self.consume(")");
+
+                report(match tok.as_str() {
+                    "transpose" => matrix::transpose(&node),
+                    "det" => matrix::det(&node),
+                    "inv" => matrix::inv(&node),
+                    _ => matrix::trace(&node),
+                })
+            },
+            Token::Reserved(tok) if tok == "grad" || tok == "divergence" || tok == "curl" || tok == "laplacian" => {
+                self.consume("(");
+                let node = self.expr()?;
+                self.consume(",");
+                let vars = self.expr()?;
+                let coords = if self.expect(",") {
+                    let name = self.next_ident()?;
+                    match Coords::from_name(&name) {
+                        Some(coords) => coords,
+                        None => {
+                            println!("\x1b[31merror\x1b[39m: unknown coordinate system '{}'", name);
+                            return Err(());
+                        },
+                    }
+                } else {
+                    Coords::Cartesian
+                };
+                self.consume(")");
+
+                report(match tok.as_str() {
+                    "grad" => vector::grad(&node, &vars, coords),
+                    "divergence" => vector::divergence(&node, &vars, coords),
+                    "curl" => vector::curl(&node, &vars, coords),
+                    _ => vector::laplacian(&node, &vars, coords),
+                })
+            },
+            Token::Reserved(tok) if tok == "directional" => {
+                self.consume("(");
+                let node = self.expr()?;
+                self.consume(",");
+                let vars = self.expr()?;
+                self.consume(",");
+                let direction = self.expr()?;
+                self.consume(")");
+
+                report(vector::directional(&node, &vars, &direction))
+            },
+            Token::Reserved(tok) if tok == "implicit_dif" => {
+                self.consume("(");
+                let relation = self.expr()?;
+                self.consume(",");
+                let y = self.expr()?;
+                self.consume(",");
+                let x = self.expr()?;
+                let n = if self.expect(",") { Some(self.expr()?) } else { None };
+                self.consume(")");
+
+                let n = match n {
+                    Some(n) => match report(eval::<f64>(&n, &Env::new()))? {
+                        n if n > implicit::MAX_ORDER as f64 => {
+                            println!("\x1b[31merror\x1b[39m: the order is at most {}, found {}", implicit::MAX_ORDER, n);
+                            return Err(());
+                        },
+                        n if n.fract() == 0.0 && n >= 0.0 => n as usize,
+                        n => {
+                            println!("\x1b[31merror\x1b[39m: expected a whole order, found {}", n);
+                            return Err(());
+                        },
+                    },
+                    None => 1,
+                };
+                report(implicit::implicit_dif(&relation, &y, &x, n))
+            },
+            Token::Reserved(tok) if tok == "euler_lagrange" => {
+                self.consume("(");
+                let l = self.expr()?;
+                self.consume(",");
+                let coords = self.expr()?;
+                self.consume(",");
+                let t = self.expr()?;
+                self.consume(")");
+
+                report(lagrange::euler_lagrange(&l, &coords, &t))
+            },
+            Token::Reserved(tok) if tok == "gcd" => {
+                self.consume("(");
+                let p = self.expr()?;
+                self.consume(",");
+                let q = self.expr()?;
+                self.consume(")");
+
+                report(factor::gcd_of(&p, &q))
+            },
+            Token::Reserved(tok) if tok == "limit" => {
+                self.consume("(");
+                let node = self.expr()?;
+                self.consume(",");
+                let var = self.expr()?;
+                self.consume(",");
+                let point = self.expr()?;
+                let mut side = None;
+                if self.expect(",") {
+                    if self.expect("+") {
+                        side = Some(1.0);
+                    } else if self.expect("-") {
+                        side = Some(-1.0);
+                    } else {
+                        println!("\x1b[31merror\x1b[39m: expected '+' or '-'");
+                        return Err(());
+                    }
+                }
+                self.consume(")");
+
+                report(limit::limit(&node, &var, &point, side))
+            },
+            Token::Reserved(tok) if tok == "[" => {
+                self.pos -= 1;
+                Ok(Node::list(self.list()?))
             },
             Token::Ident(ident) => {
                 if let Some(node) = self.symbol_table.find(ident.clone()) {
@@ -297,13 +885,16 @@ impl Parser {
                         if self.expect(")") { break; }
                     }
 
-                    return Ok(op.construct(op.cont.clone(), params));
+                    return report(op.construct(&op.cont, &params, &mut HashMap::new()));
+                }
+                if let Some(kind) = CKind::from_name(&ident) {
+                    return Ok(Node::constant(kind));
                 }
                 println!("\x1b[31merror\x1b[39m: expected an identifier");
                 Err(())
             },
             Token::Num(val) => {
-                Ok(Node::Num { val: val as f32 })
+                Ok(Node::num(val))
             },
             _ => {
                 println!("\x1b[31merror\x1b[39m: unexpected token");
@@ -311,6 +902,86 @@ impl Parser {
             },
         }
     }
+    fn list(&mut self) -> Result<Vec<Rc<Node>>,()> {
+        let mut nodes = Vec::new();
+        self.consume("[");
+        if self.expect("]") {
+            return Ok(nodes);
+        }
+        loop {
+            nodes.push(self.expr()?);
+            if self.expect(",") { continue; }
+            if self.expect("]") { break; }
+            println!("\x1b[31merror\x1b[39m: expected ']'");
+            return Err(());
+        }
+        Ok(nodes)
+    }
+
+    // A single expression, or a bracketed list of them, each with its source text.
+    fn exprs(&mut self) -> Result<Vec<(Rc<Node>, String)>,()> {
+        if !self.expect("[") {
+            return Ok(vec![self.labelled()?]);
+        }
+        let mut nodes = Vec::new();
+        if self.expect("]") {
+            return Ok(nodes);
+        }
+        loop {
+            nodes.push(self.labelled()?);
+            if self.expect(",") { continue; }
+            if self.expect("]") { break; }
+            println!("\x1b[31merror\x1b[39m: expected ']'");
+            return Err(());
+        }
+        Ok(nodes)
+    }
+
+    // An expression and its source text, which labels output without printing
+    // the expression, as that can be exponentially long once derivatives are taken.
+    fn labelled(&mut self) -> Result<(Rc<Node>, String),()> {
+        let from = self.pos;
+        let node = self.expr()?;
+        Ok((node, source(&self.token_list[from..self.pos])))
+    }
+
+    // `for x in from..to`, optionally followed by `step s`.
+    fn range(&mut self) -> Result<(String, f64, f64, Option<f64>),()> {
+        self.consume_ident("for")?;
+        let var = self.next_ident()?;
+        self.consume_ident("in")?;
+        let from = self.expr()?;
+        self.consume(".");
+        self.consume(".");
+        let to = self.expr()?;
+        let step = if self.expect_ident("step") { Some(self.expr()?) } else { None };
+
+        let from = report(eval::<f64>(&from, &Env::new()))?;
+        let to = report(eval::<f64>(&to, &Env::new()))?;
+        let step = match step {
+            Some(step) => Some(report(eval::<f64>(&step, &Env::new()))?),
+            None => None,
+        };
+        Ok((var, from, to, step))
+    }
+
+    // An optional `to "path"`.
+    fn destination(&mut self) -> Result<Option<String>,()> {
+        if !self.expect_ident("to") {
+            return Ok(None);
+        }
+        match &self.token_list[self.pos] {
+            Str(text) => {
+                let text = text.clone();
+                self.pos += 1;
+                Ok(Some(text))
+            },
+            _ => {
+                println!("\x1b[31merror\x1b[39m: expected a file name");
+                Err(())
+            },
+        }
+    }
 
     fn inc(&mut self) {
         self.pos += 1;
@@ -328,6 +999,10 @@ impl Parser {
         }
     }
 
+    fn peek(&self, name: &str) -> bool {
+        matches!(&self.token_list[self.pos], Reserved(symbol) if symbol == name)
+    }
+
     fn consume(&mut self, name: &str) {
         match &self.token_list[self.pos] {
             Reserved(symbol) if symbol == name => {
@@ -339,6 +1014,44 @@ impl Parser {
         }
     }
 
+    // Whether the identifier `name` at the current position calls a builtin.
+    fn is_builtin(&mut self, name: &str) -> bool {
+        BUILTINS.contains(&name)
+            && matches!(self.token_list.get(self.pos + 1), Some(Reserved(symbol)) if symbol == "(")
+            && self.symbol_table.find(name.to_string()).is_none()
+            && self.op_table.find(name.to_string()).is_none()
+    }
+
+    fn expect_builtin(&mut self, name: &str) -> bool {
+        if matches!(&self.token_list[self.pos], Ident(ident) if ident == name) && self.is_builtin(name) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect_ident(&mut self, name: &str) -> bool {
+        match &self.token_list[self.pos] {
+            Ident(ident) if ident == name => {
+                self.pos += 1;
+                true
+            },
+            _ => {
+                false
+            },
+        }
+    }
+
+    fn consume_ident(&mut self, name: &str) -> Result<(),()> {
+        if self.expect_ident(name) {
+            Ok(())
+        } else {
+            println!("\x1b[31merror\x1b[39m: expected '{}'", name);
+            Err(())
+        }
+    }
+
     fn next_ident(&mut self) -> Result<String,()> {
         match &self.token_list[self.pos] {
             Ident(ident) => {
@@ -351,4 +1064,69 @@ impl Parser {
             }
         }
     }
+}
+
+// Writes `tokens` back out as source text, spaced the way expressions print.
+fn source(tokens: &[Token]) -> String {
+    let symbol = |i: usize| match tokens.get(i) {
+        Some(Reserved(symbol)) if !symbol.chars().all(char::is_alphanumeric) => Some(symbol.as_str()),
+        _ => None,
+    };
+    let mut text = String::new();
+    for (i, token) in tokens.iter().enumerate() {
+        let tight = i == 0
+            || matches!(symbol(i), Some(")" | "]" | ","))
+            || matches!(symbol(i - 1), Some("(" | "["))
+            // A call, as in `sin(x)`.
+            || (symbol(i) == Some("(") && symbol(i - 1).is_none())
+            // A negation, as in `-x` or `2 * -x`.
+            || (symbol(i - 1) == Some("-") && (i == 1 || !matches!(symbol(i - 2), None | Some(")" | "]"))));
+        if !tight {
+            text.push(' ');
+        }
+        match token {
+            Reserved(name) | Ident(name) => text.push_str(name),
+            Num(val) => text.push_str(&val.to_string()),
+            Str(string) => text.push_str(&format!("{:?}", string)),
+        }
+    }
+    text
+}
+
+// Parses the expression after the last `;` of `src`, following the statements
+// before it, as in `var x; sin(x) / x`. Tests of other modules build their
+// input this way.
+#[cfg(test)]
+pub fn parse(src: &str) -> Rc<Node> {
+    let (stmts, expr) = src.rsplit_once(';').unwrap_or(("", src));
+    let stmts = if stmts.is_empty() { String::new() } else { format!("{};", stmts) };
+    let len = Lexer::new(stmts.clone()).tokenize().len();
+    let mut parser = Parser::new(&Lexer::new(format!("{} {};", stmts, expr)).tokenize());
+    while parser.pos < len {
+        parser.stmt().unwrap();
+    }
+    parser.expr().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn labels_keep_the_source_text() {
+        let tokens = Lexer::new("var x; table [sin(x) / x, 2 * -x, (x + 1) ^ 2, abs(-3 - x)] for x in 0..1;".to_string()).tokenize();
+        let mut parser = Parser::new(&tokens);
+        parser.stmt().unwrap();
+        parser.pos += 1;
+        let labels = parser.exprs().unwrap().into_iter().map(|(_, label)| label).collect::<Vec<String>>();
+        assert_eq!(labels, ["sin(x) / x", "2 * -x", "(x + 1) ^ 2", "abs(-3 - x)"]);
+    }
+
+    #[test]
+    fn builtin_names_are_identifiers() {
+        assert_eq!(parse("var x, det, re; re = 2; det * x + re").to_string(), "det * x + 2");
+        assert_eq!(parse("var x; det([[x, 1], [1, x]]) + re(x)").to_string(), "x ^ 2 - 1 + re(x)");
+        assert_eq!(parse("var x; op inv(a) { 1 / a }; inv(x)").to_string(), "1 / x");
+        assert_eq!(parse("var x, abs = 3; abs * x").to_string(), "3 * x");
+    }
 }
\ No newline at end of file
@@ -0,0 +1,237 @@
+use crate::bytecode::Program;
+
+const WIDTH: f64 = 640.0;
+const HEIGHT: f64 = 400.0;
+const LEFT: f64 = 60.0;
+const RIGHT: f64 = 20.0;
+const TOP: f64 = 20.0;
+const BOTTOM: f64 = 40.0;
+const COLORS: [&str; 6] = ["#1f77b4", "#d62728", "#2ca02c", "#ff7f0e", "#9467bd", "#8c564b"];
+
+pub struct Curve {
+    pub label: String,
+    pub program: Program,
+}
+
+// `count` evenly spaced samples of `program` over `from..to`.
+pub fn sample(program: &Program, from: f64, to: f64, count: usize) -> (Vec<f64>, Vec<f64>) {
+    let xs = (0..count).map(|i| from + (to - from) * i as f64 / (count - 1) as f64).collect::<Vec<f64>>();
+    let mut ys = vec![0.0; count];
+    program.run(&[&xs], &mut ys);
+    (xs, ys)
+}
+
+// The vertical range to show. Values near an asymptote would flatten everything
+// else, so when the extremes are far outside the bulk of the samples the range is
+// taken from the 5th to 95th percentile instead.
+pub fn y_range(samples: &[&[f64]]) -> (f64, f64) {
+    let mut finite = samples.iter().flat_map(|ys| ys.iter()).copied().filter(|y| y.is_finite()).collect::<Vec<f64>>();
+    if finite.is_empty() {
+        return (-1.0, 1.0);
+    }
+    finite.sort_by(f64::total_cmp);
+    let (min, max) = (finite[0], finite[finite.len() - 1]);
+    let (p05, p95) = (finite[finite.len() / 20], finite[finite.len() * 19 / 20]);
+    let (lo, hi) = if max - min > 20.0 * (p95 - p05) && p95 > p05 {
+        let pad = (p95 - p05) * 0.5;
+        (p05 - pad, p95 + pad)
+    } else {
+        (min, max)
+    };
+    if hi - lo < 1e-12 {
+        return (lo - 1.0, hi + 1.0);
+    }
+    let pad = (hi - lo) * 0.05;
+    (lo - pad, hi + pad)
+}
+
+// Whether the curve jumps between two neighbouring samples instead of passing
+// through the values in between, as it does at a pole or a step.
+pub fn is_break(program: &Program, (x0, y0): (f64, f64), (x1, y1): (f64, f64), height: f64) -> bool {
+    if !y0.is_finite() || !y1.is_finite() {
+        return true;
+    }
+    if (y1 - y0).abs() < height * 0.5 {
+        return false;
+    }
+    let mid = program.at(&[(x0 + x1) * 0.5]);
+    let slack = (y1 - y0).abs() * 0.1;
+    !(mid >= y0.min(y1) - slack && mid <= y0.max(y1) + slack)
+}
+
+// Tick positions at a round step (1, 2 or 5 times a power of ten) giving about
+// `count` ticks over `lo..hi`.
+pub fn ticks(lo: f64, hi: f64, count: usize) -> (Vec<f64>, f64) {
+    let raw = (hi - lo) / count as f64;
+    let magnitude = 10f64.powf(raw.log10().floor());
+    let step = [1.0, 2.0, 5.0, 10.0].iter().map(|m| m * magnitude).find(|step| *step >= raw).unwrap_or(10.0 * magnitude);
+    let first = (lo / step).ceil() as i64;
+    let last = (hi / step).floor() as i64;
+    ((first..=last).map(|i| i as f64 * step).collect(), step)
+}
+
+pub fn tick_label(val: f64, step: f64) -> String {
+    let decimals = (-step.log10().floor()).max(0.0) as usize;
+    let text = format!("{:.1$}", val, decimals);
+    if text.trim_start_matches('-').chars().all(|c| c == '0' || c == '.') { "0".to_string() } else { text }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+// Renders every curve over `from..to` as a standalone SVG document.
+pub fn svg(curves: &[Curve], from: f64, to: f64) -> String {
+    let samples = curves.iter().map(|curve| sample(&curve.program, from, to, 1000)).collect::<Vec<(Vec<f64>, Vec<f64>)>>();
+    let (lo, hi) = y_range(&samples.iter().map(|(_, ys)| ys.as_slice()).collect::<Vec<&[f64]>>());
+    let (w, h) = (WIDTH - LEFT - RIGHT, HEIGHT - TOP - BOTTOM);
+    let px = |x: f64| LEFT + (x - from) / (to - from) * w;
+    // Points far outside the view are clamped so that coordinates stay finite.
+    let py = |y: f64| TOP + (hi - y.clamp(lo - 10.0 * (hi - lo), hi + 10.0 * (hi - lo))) / (hi - lo) * h;
+
+    let mut svg = String::new();
+    svg.push_str(&format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\" font-family=\"sans-serif\" font-size=\"12\">\n", WIDTH, HEIGHT, WIDTH, HEIGHT));
+    svg.push_str(&format!("<rect width=\"{}\" height=\"{}\" fill=\"white\"/>\n", WIDTH, HEIGHT));
+    svg.push_str(&format!("<clipPath id=\"area\"><rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"/></clipPath>\n", LEFT, TOP, w, h));
+
+    let (xticks, xstep) = ticks(from, to, 8);
+    for x in xticks {
+        svg.push_str(&format!("<line x1=\"{0:.2}\" y1=\"{1}\" x2=\"{0:.2}\" y2=\"{2}\" stroke=\"#e0e0e0\"/>\n", px(x), TOP, TOP + h));
+        svg.push_str(&format!("<text x=\"{:.2}\" y=\"{}\" text-anchor=\"middle\">{}</text>\n", px(x), TOP + h + 16.0, tick_label(x, xstep)));
+    }
+    let (yticks, ystep) = ticks(lo, hi, 6);
+    for y in yticks {
+        svg.push_str(&format!("<line x1=\"{0}\" y1=\"{1:.2}\" x2=\"{2}\" y2=\"{1:.2}\" stroke=\"#e0e0e0\"/>\n", LEFT, py(y), LEFT + w));
+        svg.push_str(&format!("<text x=\"{}\" y=\"{:.2}\" text-anchor=\"end\" dominant-baseline=\"middle\">{}</text>\n", LEFT - 6.0, py(y), tick_label(y, ystep)));
+    }
+    if from < 0.0 && to > 0.0 {
+        svg.push_str(&format!("<line x1=\"{0:.2}\" y1=\"{1}\" x2=\"{0:.2}\" y2=\"{2}\" stroke=\"#808080\"/>\n", px(0.0), TOP, TOP + h));
+    }
+    if lo < 0.0 && hi > 0.0 {
+        svg.push_str(&format!("<line x1=\"{0}\" y1=\"{1:.2}\" x2=\"{2}\" y2=\"{1:.2}\" stroke=\"#808080\"/>\n", LEFT, py(0.0), LEFT + w));
+    }
+    svg.push_str(&format!("<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"none\" stroke=\"black\"/>\n", LEFT, TOP, w, h));
+
+    for (i, (curve, (xs, ys))) in curves.iter().zip(&samples).enumerate() {
+        let mut path = String::new();
+        let mut pen_down = false;
+        for j in 0..xs.len() {
+            if !ys[j].is_finite() {
+                pen_down = false;
+                continue;
+            }
+            if j > 0 && pen_down && is_break(&curve.program, (xs[j - 1], ys[j - 1]), (xs[j], ys[j]), hi - lo) {
+                pen_down = false;
+            }
+            path.push_str(&format!("{}{:.2},{:.2} ", if pen_down { "L" } else { "M" }, px(xs[j]), py(ys[j])));
+            pen_down = true;
+        }
+        svg.push_str(&format!("<path d=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"1.5\" clip-path=\"url(#area)\"/>\n", path.trim_end(), COLORS[i % COLORS.len()]));
+    }
+
+    let legend_width = curves.iter().map(|curve| curve.label.chars().count()).max().unwrap_or(0) as f64 * 7.0 + 40.0;
+    svg.push_str(&format!("<rect x=\"{}\" y=\"{}\" width=\"{:.0}\" height=\"{}\" fill=\"white\" fill-opacity=\"0.85\" stroke=\"#c0c0c0\"/>\n", LEFT + 8.0, TOP + 8.0, legend_width, curves.len() as f64 * 18.0 + 8.0));
+    for (i, curve) in curves.iter().enumerate() {
+        let y = TOP + 21.0 + i as f64 * 18.0;
+        svg.push_str(&format!("<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"{}\" stroke-width=\"2\"/>\n", LEFT + 14.0, y, LEFT + 34.0, y, COLORS[i % COLORS.len()]));
+        svg.push_str(&format!("<text x=\"{}\" y=\"{}\" dominant-baseline=\"middle\">{}</text>\n", LEFT + 40.0, y, escape(&curve.label)));
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+// Renders every curve over `from..to` as `width` by `height` characters, each
+// holding 2 by 4 Braille dots or 1 by 2 half blocks. Columns where a curve is
+// undefined are shaded, and with `color` each curve gets its own ANSI color.
+pub fn text(curves: &[Curve], from: f64, to: f64, width: usize, height: usize, braille: bool, color: bool) -> String {
+    let (cell_w, cell_h) = if braille { (2, 4) } else { (1, 2) };
+    let (dots_w, dots_h) = (width * cell_w, height * cell_h);
+    let samples = curves.iter().map(|curve| sample(&curve.program, from, to, dots_w)).collect::<Vec<(Vec<f64>, Vec<f64>)>>();
+    let (lo, hi) = y_range(&samples.iter().map(|(_, ys)| ys.as_slice()).collect::<Vec<&[f64]>>());
+    let row = |y: f64| ((hi - y) / (hi - lo) * (dots_h - 1) as f64).round().clamp(-1.0, dots_h as f64) as isize;
+
+    // 0 marks an axis dot and i + 1 a dot of curve i, drawn over the axes.
+    let mut dots = vec![vec![None; dots_w]; dots_h];
+    let mut undefined = vec![false; width];
+    if lo < 0.0 && hi > 0.0 {
+        dots[row(0.0) as usize].fill(Some(0));
+    }
+    if from < 0.0 && to > 0.0 {
+        let j = ((-from) / (to - from) * (dots_w - 1) as f64).round() as usize;
+        for r in dots.iter_mut() {
+            r[j] = Some(0);
+        }
+    }
+    for (i, (curve, (xs, ys))) in curves.iter().zip(&samples).enumerate() {
+        for j in 0..dots_w {
+            if !ys[j].is_finite() {
+                undefined[j / cell_w] = true;
+                continue;
+            }
+            let r = row(ys[j]);
+            let (mut top, mut bottom) = (r, r);
+            if j > 0 && !is_break(&curve.program, (xs[j - 1], ys[j - 1]), (xs[j], ys[j]), hi - lo) {
+                let prev = row(ys[j - 1]);
+                top = top.min((prev + r) / 2);
+                bottom = bottom.max((prev + r) / 2);
+            }
+            for r in top.max(0)..=bottom.min(dots_h as isize - 1) {
+                dots[r as usize][j] = Some(i + 1);
+            }
+        }
+    }
+
+    let labels = [tick_label(hi, (hi - lo) / 100.0), tick_label(lo, (hi - lo) / 100.0)];
+    let margin = labels.iter().map(|label| label.chars().count()).max().unwrap_or(0);
+    let mut text = String::new();
+    for r in 0..height {
+        let label = match r {
+            0 => &labels[0],
+            _ if r == height - 1 => &labels[1],
+            _ => "",
+        };
+        text.push_str(&format!("{:>1$} ┤", label, margin));
+        for c in 0..width {
+            let mut bits = 0;
+            let mut owner = 0;
+            for dr in 0..cell_h {
+                for dc in 0..cell_w {
+                    if let Some(layer) = dots[r * cell_h + dr][c * cell_w + dc] {
+                        bits |= if braille { BRAILLE[dc][dr] } else { 1 << dr };
+                        owner = owner.max(layer);
+                    }
+                }
+            }
+            let ch = match (bits, braille) {
+                (0, _) if undefined[c] => '░',
+                (0, _) => ' ',
+                (_, true) => char::from_u32(0x2800 + bits).unwrap(),
+                (1, false) => '▀',
+                (2, false) => '▄',
+                (_, false) => '█',
+            };
+            if color && owner > 0 {
+                text.push_str(&format!("\x1b[{}m{}\x1b[39m", ANSI[(owner - 1) % ANSI.len()], ch));
+            } else {
+                text.push(ch);
+            }
+        }
+        text.push('\n');
+    }
+    text.push_str(&format!("{:>1$} └{2}\n", "", margin, "─".repeat(width)));
+    let (left, right) = (tick_label(from, (to - from) / 100.0), tick_label(to, (to - from) / 100.0));
+    let gap = (width + 1).saturating_sub(left.chars().count() + right.chars().count());
+    text.push_str(&format!("{:>1$} {2}{3}{4}\n", "", margin, left, " ".repeat(gap), right));
+    for (i, curve) in curves.iter().enumerate() {
+        if color {
+            text.push_str(&format!("  \x1b[{}m━━\x1b[39m {}\n", ANSI[i % ANSI.len()], curve.label));
+        } else {
+            text.push_str(&format!("  {} {}\n", i + 1, curve.label));
+        }
+    }
+    text
+}
+
+// Bit of the Braille dot in column `dc` and row `dr` of a character cell.
+const BRAILLE: [[u32; 4]; 2] = [[0x01, 0x02, 0x04, 0x40], [0x08, 0x10, 0x20, 0x80]];
+const ANSI: [u32; 6] = [34, 31, 32, 33, 35, 36];
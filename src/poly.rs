@@ -0,0 +1,595 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::rc::Rc;
+
+use crate::eval::*;
+use crate::node::*;
+use crate::rational::{gcd, overflow, Rational};
+use crate::simplify::simplify;
+
+use BKind::*;
+use UKind::*;
+use Node::*;
+
+// Integer powers above this are not expanded.
+pub const MAX_POWER: i32 = 64;
+
+pub fn depends(node: &Rc<Node>, var: &str) -> bool {
+    Node::free_vars(node).iter().any(|name| name == var)
+}
+
+pub fn is_zero(node: &Rc<Node>) -> bool {
+    matches!(**node, Num { val } if val == 0.0)
+}
+
+fn add(a: &[Rc<Node>], b: &[Rc<Node>], kind: BKind) -> Vec<Rc<Node>> {
+    let zero = Node::num(0.0);
+    (0..a.len().max(b.len()))
+        .map(|i| simplify(&Node::binary(kind, a.get(i).unwrap_or(&zero).clone(), b.get(i).unwrap_or(&zero).clone())))
+        .collect()
+}
+
+fn mul(a: &[Rc<Node>], b: &[Rc<Node>]) -> Vec<Rc<Node>> {
+    let mut res = vec![Node::num(0.0); a.len() + b.len() - 1];
+    for (i, a) in a.iter().enumerate() {
+        for (j, b) in b.iter().enumerate() {
+            res[i + j] = simplify(&Node::binary(Add, res[i + j].clone(), Node::binary(Mul, a.clone(), b.clone())));
+        }
+    }
+    res
+}
+
+fn trim(mut coeffs: Vec<Rc<Node>>) -> Vec<Rc<Node>> {
+    while coeffs.len() > 1 && is_zero(coeffs.last().unwrap()) {
+        coeffs.pop();
+    }
+    coeffs
+}
+
+// The coefficients of `node` as a polynomial in `var`, lowest power first, or
+// `None` when `var` appears other than in sums, products and constant powers.
+pub fn coefficients(node: &Rc<Node>, var: &str) -> Option<Vec<Rc<Node>>> {
+    if !depends(node, var) {
+        return Some(vec![simplify(node)]);
+    }
+    let coeffs = match &**node {
+        Var { point: Some(point), .. } => return coefficients(point, var),
+        Var { .. } => vec![Node::num(0.0), Node::num(1.0)],
+        BinaryOperator { kind: kind @ (Add | Sub), lhs, rhs } => add(&coefficients(lhs, var)?, &coefficients(rhs, var)?, *kind),
+        BinaryOperator { kind: Mul, lhs, rhs } => mul(&coefficients(lhs, var)?, &coefficients(rhs, var)?),
+        BinaryOperator { kind: Div, lhs, rhs } if !depends(rhs, var) => {
+            coefficients(lhs, var)?.iter().map(|coeff| simplify(&Node::binary(Div, coeff.clone(), rhs.clone()))).collect()
+        },
+        BinaryOperator { kind: Pow, lhs, rhs } => {
+            let n = match **rhs {
+                Num { val } if val >= 0.0 && val.fract() == 0.0 && val <= MAX_POWER as f32 => val as usize,
+                _ => return None,
+            };
+            let base = coefficients(lhs, var)?;
+            let mut res = vec![Node::num(1.0)];
+            for _ in 0..n {
+                res = mul(&res, &base);
+            }
+            res
+        },
+        UnaryOperator { kind: Neg, operand } => {
+            coefficients(operand, var)?.iter().map(|coeff| simplify(&Node::unary(Neg, coeff.clone()))).collect()
+        },
+        _ => return None,
+    };
+    Some(trim(coeffs))
+}
+
+// What monomials are products of: variables, and subexpressions that are not
+// polynomials, such as `sin(x)` or `x ^ 0.5`, named by how they print.
+// Variables sort first.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Atom {
+    Var(String),
+    Other(String),
+}
+
+// Atoms with their nonzero exponents, sorted by atom. Negative exponents put an
+// atom in the denominator.
+type Monomial = Vec<(Atom, i32)>;
+
+fn mul_monomials(a: &Monomial, b: &Monomial) -> Monomial {
+    let mut res = BTreeMap::new();
+    for (atom, exp) in a.iter().chain(b) {
+        *res.entry(atom.clone()).or_insert(0) += exp;
+    }
+    res.into_iter().filter(|(_, exp)| *exp != 0).collect()
+}
+
+fn degree_of(mono: &Monomial) -> i32 {
+    mono.iter().map(|(_, exp)| exp).sum()
+}
+
+fn exponent(mono: &Monomial, atom: &Atom) -> i32 {
+    mono.iter().find(|(atom_, _)| atom_ == atom).map_or(0, |(_, exp)| *exp)
+}
+
+// Higher powers of the atoms that sort first first. Unlike the order of the
+// monomial vectors themselves, this is compatible with multiplication, so the
+// leading term of a product is the product of the leading terms.
+fn lex_order(a: &Monomial, b: &Monomial) -> Ordering {
+    let mut atoms = a.iter().chain(b).map(|(atom, _)| atom).collect::<Vec<&Atom>>();
+    atoms.sort();
+    atoms.iter().map(|atom| exponent(a, atom).cmp(&exponent(b, atom))).find(|ord| ord.is_ne()).unwrap_or(Ordering::Equal)
+}
+
+// Higher total degree first, then higher powers of the atoms that sort first.
+fn term_order(a: &Monomial, b: &Monomial) -> Ordering {
+    degree_of(b).cmp(&degree_of(a)).then_with(|| {
+        let mut atoms = a.iter().chain(b).map(|(atom, _)| atom).collect::<Vec<&Atom>>();
+        atoms.sort();
+        atoms.iter().map(|atom| exponent(b, atom).cmp(&exponent(a, atom))).find(|ord| ord.is_ne()).unwrap_or(Ordering::Equal)
+    })
+}
+
+// Negates a product by its first factor, so that it prints as `-2 * x` or
+// `-y * x` rather than `-(2 * x)`. A power keeps the parentheses, since `-x ^ 2`
+// would square `-x`.
+pub fn negate_leading(node: &Rc<Node>) -> Rc<Node> {
+    match &**node {
+        BinaryOperator { kind: kind @ (Mul | Div), lhs, rhs } => Node::binary(*kind, negate_leading(lhs), rhs.clone()),
+        Num { val } => Node::num(-val),
+        _ => Node::unary(Neg, node.clone()),
+    }
+}
+
+// `c` as a constant. Constants are `f32`s, so coefficients that no `f32` holds
+// exactly are an error rather than being rounded.
+pub fn number(c: Rational) -> Result<Rc<Node>, String> {
+    c.to_f32().map(Node::num).ok_or_else(|| format!("the coefficient {} is too large to represent exactly", c))
+}
+
+fn binomial(n: u32, k: u32) -> i128 {
+    (0..k as i128).fold(1, |acc, i| acc * (n as i128 - i) / (i + 1))
+}
+
+// A sparse multivariate polynomial with exact rational coefficients.
+#[derive(Debug, Clone)]
+pub struct Poly {
+    terms: BTreeMap<Monomial, Rational>,
+    nodes: HashMap<Atom, Rc<Node>>,
+}
+
+impl Poly {
+    pub fn constant(c: Rational) -> Poly {
+        let mut terms = BTreeMap::new();
+        if !c.is_zero() {
+            terms.insert(Vec::new(), c);
+        }
+        Poly { terms, nodes: HashMap::new() }
+    }
+
+    pub fn one() -> Poly {
+        Poly::constant(Rational::int(1))
+    }
+
+    pub fn atom(node: Rc<Node>, exp: i32) -> Poly {
+        let atom = match &*node {
+            Var { name, point: None, .. } => Atom::Var(name.clone()),
+            _ => Atom::Other(node.to_string()),
+        };
+        let mut terms = BTreeMap::new();
+        terms.insert(vec![(atom.clone(), exp)], Rational::int(1));
+        Poly { terms, nodes: HashMap::from([(atom, node)]) }
+    }
+
+    fn merge_nodes(&self, other: &Poly) -> HashMap<Atom, Rc<Node>> {
+        let mut nodes = self.nodes.clone();
+        nodes.extend(other.nodes.iter().map(|(atom, node)| (atom.clone(), node.clone())));
+        nodes
+    }
+
+    pub fn add(&self, other: &Poly) -> Result<Poly, String> {
+        let mut terms = self.terms.clone();
+        for (mono, c) in &other.terms {
+            let sum = terms.get(mono).map_or(Ok(*c), |c_| c_.checked_add(*c))?;
+            if sum.is_zero() {
+                terms.remove(mono);
+            } else {
+                terms.insert(mono.clone(), sum);
+            }
+        }
+        Ok(Poly { terms, nodes: self.merge_nodes(other) })
+    }
+
+    pub fn neg(&self) -> Poly {
+        Poly { terms: self.terms.iter().map(|(mono, c)| (mono.clone(), -*c)).collect(), nodes: self.nodes.clone() }
+    }
+
+    pub fn sub(&self, other: &Poly) -> Result<Poly, String> {
+        self.add(&other.neg())
+    }
+
+    pub fn mul(&self, other: &Poly) -> Result<Poly, String> {
+        let mut res = Poly { terms: BTreeMap::new(), nodes: self.merge_nodes(other) };
+        for (mono1, c1) in &self.terms {
+            for (mono2, c2) in &other.terms {
+                let term = Poly { terms: BTreeMap::from([(mul_monomials(mono1, mono2), c1.checked_mul(*c2)?)]), nodes: HashMap::new() };
+                res = res.add(&term)?;
+            }
+        }
+        Ok(res)
+    }
+
+    // The multinomial theorem: the power of `t1 + ... + tm` is the sum over
+    // `k1 + ... + km = n` of `n! / (k1! ... km!) * t1^k1 ... tm^km`.
+    pub fn pow(&self, n: u32) -> Result<Poly, String> {
+        fn expand(terms: &[(&Monomial, &Rational)], left: u32, mono: Monomial, c: Rational, res: &mut Poly) -> Result<(), String> {
+            let Some(((mono_i, c_i), rest)) = terms.split_first() else {
+                if left == 0 {
+                    *res = res.add(&Poly { terms: BTreeMap::from([(mono, c)]), nodes: HashMap::new() })?;
+                }
+                return Ok(());
+            };
+            let ks = if rest.is_empty() { left..=left } else { 0..=left };
+            for k in ks {
+                let power = mono_i.iter().map(|(atom, exp)| (atom.clone(), exp * k as i32)).collect::<Monomial>();
+                let c = c.checked_mul(Rational::int(binomial(left, k)))?.checked_mul(c_i.checked_pow(k as i32)?)?;
+                expand(rest, left - k, mul_monomials(&mono, &power), c, res)?;
+            }
+            Ok(())
+        }
+        let terms = self.terms.iter().collect::<Vec<(&Monomial, &Rational)>>();
+        if terms.is_empty() {
+            return Ok(Poly::constant(Rational::int(if n == 0 { 1 } else { 0 })));
+        }
+        let mut res = Poly { terms: BTreeMap::new(), nodes: self.nodes.clone() };
+        expand(&terms, n, Vec::new(), Rational::int(1), &mut res)?;
+        Ok(res)
+    }
+
+    pub fn as_constant(&self) -> Option<Rational> {
+        match self.terms.len() {
+            0 => Some(Rational::int(0)),
+            1 => self.terms.get(&Vec::new()).copied(),
+            _ => None,
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    // The atoms that occur, in order.
+    pub fn atoms(&self) -> Vec<Atom> {
+        let mut atoms = self.terms.keys().flatten().map(|(atom, _)| atom.clone()).collect::<Vec<Atom>>();
+        atoms.sort();
+        atoms.dedup();
+        atoms
+    }
+
+    pub fn total_degree(&self) -> i32 {
+        self.terms.keys().map(degree_of).max().unwrap_or(0)
+    }
+
+    pub fn degree_in(&self, atom: &Atom) -> i32 {
+        self.terms.keys().map(|mono| exponent(mono, atom)).max().unwrap_or(0)
+    }
+
+    // `atom ^ n`, for an atom that occurs in `self`.
+    pub fn power_of(&self, atom: &Atom, n: i32) -> Poly {
+        let mono = if n == 0 { Vec::new() } else { vec![(atom.clone(), n)] };
+        Poly { terms: BTreeMap::from([(mono, Rational::int(1))]), nodes: self.nodes.clone() }
+    }
+
+    pub fn derivative(&self, atom: &Atom) -> Result<Poly, String> {
+        let mut res = Poly { terms: BTreeMap::new(), nodes: self.nodes.clone() };
+        for (mono, c) in &self.terms {
+            let n = exponent(mono, atom);
+            if n != 0 {
+                let mono = mul_monomials(mono, &vec![(atom.clone(), -1)]);
+                res.terms.insert(mono, c.checked_mul(Rational::int(n as i128))?);
+            }
+        }
+        Ok(res)
+    }
+
+    fn leading(&self) -> Option<(&Monomial, &Rational)> {
+        self.terms.iter().max_by(|(a, _), (b, _)| lex_order(a, b))
+    }
+
+    // `self / other` when `other` divides `self` exactly, and `None` when it
+    // does not.
+    pub fn div_exact(&self, other: &Poly) -> Result<Option<Poly>, String> {
+        let Some((lead, c)) = other.leading() else { return Ok(None) };
+        let mut rem = self.clone();
+        let mut res = Poly { terms: BTreeMap::new(), nodes: self.merge_nodes(other) };
+        while let Some((mono, c_)) = rem.leading() {
+            let quotient = mul_monomials(mono, &lead.iter().map(|(atom, exp)| (atom.clone(), -exp)).collect());
+            if quotient.iter().any(|(_, exp)| *exp < 0) {
+                return Ok(None);
+            }
+            let term = Poly { terms: BTreeMap::from([(quotient, c_.checked_div(*c)?)]), nodes: HashMap::new() };
+            rem = rem.sub(&term.mul(other)?)?;
+            res = res.add(&term)?;
+        }
+        Ok(Some(res))
+    }
+
+    // The positive gcd of the coefficients, signed like the leading one, so that
+    // dividing it out leaves integer coprime coefficients and a positive lead.
+    pub fn content(&self) -> Result<Rational, String> {
+        let Some((_, lead)) = self.leading() else { return Ok(Rational::int(1)) };
+        let (num, den) = self.terms.values().try_fold((0, 1), |(num, den), c| {
+            let lcm = (den / gcd(den, c.den)).checked_mul(c.den).ok_or_else(overflow)?;
+            Ok::<_, String>((gcd(num, c.num), lcm))
+        })?;
+        Rational::new(num * lead.num.signum(), den)
+    }
+
+    pub fn primitive(&self) -> Result<Poly, String> {
+        self.mul(&Poly::constant(self.content()?.recip()?))
+    }
+
+    // The integer coefficients of the Kronecker substitution `atoms[j] = t ^
+    // base ^ j`, lowest power of `t` first. It can be undone as long as no
+    // exponent reaches `base`.
+    pub fn to_dense(&self, atoms: &[Atom], base: usize) -> Vec<i128> {
+        let mut res = vec![0; base.pow(atoms.len() as u32)];
+        for (mono, c) in &self.terms {
+            let n = atoms.iter().enumerate().map(|(j, atom)| exponent(mono, atom) as usize * base.pow(j as u32)).sum::<usize>();
+            res[n] = c.num;
+        }
+        res
+    }
+
+    pub fn with_dense(&self, coeffs: &[i128], atoms: &[Atom], base: usize) -> Poly {
+        let mut terms = BTreeMap::new();
+        for (n, c) in coeffs.iter().enumerate().filter(|(_, c)| **c != 0) {
+            let mono = atoms
+                .iter()
+                .enumerate()
+                .map(|(j, atom)| (atom.clone(), (n / base.pow(j as u32) % base) as i32))
+                .filter(|(_, exp)| *exp != 0)
+                .collect();
+            terms.insert(mono, Rational::int(*c));
+        }
+        Poly { terms, nodes: self.nodes.clone() }
+    }
+
+    // `1 / self`, which only stays a polynomial for a single term. Any other
+    // divisor becomes an atom with exponent -1.
+    fn recip(&self) -> Result<Poly, String> {
+        if self.terms.len() == 1 {
+            let (mono, c) = self.terms.iter().next().unwrap();
+            let mono = mono.iter().map(|(atom, exp)| (atom.clone(), -exp)).collect::<Monomial>();
+            return Ok(Poly { terms: BTreeMap::from([(mono, c.recip()?)]), nodes: self.nodes.clone() });
+        }
+        Ok(Poly::atom(self.to_node()?, -1))
+    }
+
+    // Expands `node` fully, also inside the arguments of functions.
+    pub fn from_node(node: &Rc<Node>) -> Result<Poly, String> {
+        Ok(match &**node {
+            Num { val } => match Rational::from_f32(*val) {
+                Some(c) => Poly::constant(c),
+                None => Poly::atom(node.clone(), 1),
+            },
+            Var { point: Some(point), .. } => Poly::from_node(point)?,
+            Var { point: None, .. } | Derivative { .. } => Poly::atom(node.clone(), 1),
+            BinaryOperator { kind, lhs, rhs } => {
+                let (lhs, rhs) = (Poly::from_node(lhs)?, Poly::from_node(rhs)?);
+                match kind {
+                    Add => lhs.add(&rhs)?,
+                    Sub => lhs.sub(&rhs)?,
+                    Mul => lhs.mul(&rhs)?,
+                    Div => lhs.mul(&rhs.recip()?)?,
+                    Pow => match rhs.as_constant() {
+                        Some(n) if n.is_integer() && n.num.abs() <= MAX_POWER as i128 => {
+                            let n = n.num as i32;
+                            if n >= 0 { lhs.pow(n as u32)? } else { lhs.recip()?.pow(n.unsigned_abs())? }
+                        },
+                        _ => Poly::atom(Node::binary(Pow, lhs.to_node()?, rhs.to_node()?), 1),
+                    },
+                }
+            },
+            UnaryOperator { kind: Neg, operand } => Poly::from_node(operand)?.neg(),
+            UnaryOperator { kind, operand } => Poly::atom(Node::unary(*kind, Poly::from_node(operand)?.to_node()?), 1),
+            Const { .. } | List { .. } | Equation { .. } | Unsolved { .. } => Poly::atom(node.clone(), 1),
+        })
+    }
+
+    fn sorted_terms(&self) -> Vec<(&Monomial, &Rational)> {
+        let mut terms = self.terms.iter().collect::<Vec<(&Monomial, &Rational)>>();
+        terms.sort_by(|(a, _), (b, _)| term_order(a, b));
+        terms
+    }
+
+    // `c * mono` for a positive `c`, as a single quotient.
+    fn term_node(&self, mono: &Monomial, c: Rational) -> Result<Rc<Node>, String> {
+        let power = |atom: &Atom, exp: i32| {
+            let node = self.nodes[atom].clone();
+            if exp == 1 { node } else { Node::binary(Pow, node, Node::num(exp as f32)) }
+        };
+        let product = |factors: Vec<Rc<Node>>| factors.into_iter().reduce(|acc, factor| Node::binary(Mul, acc, factor));
+
+        let mut num = Vec::new();
+        let mut den = Vec::new();
+        if c.num != 1 || mono.iter().all(|(_, exp)| *exp < 0) {
+            num.push(number(Rational::int(c.num))?);
+        }
+        if c.den != 1 {
+            den.push(number(Rational::int(c.den))?);
+        }
+        for (atom, exp) in mono {
+            if *exp > 0 {
+                num.push(power(atom, *exp));
+            } else {
+                den.push(power(atom, -exp));
+            }
+        }
+        let num = product(num).unwrap();
+        Ok(match product(den) {
+            Some(den) => Node::binary(Div, num, den),
+            None => num,
+        })
+    }
+
+    pub fn to_node(&self) -> Result<Rc<Node>, String> {
+        let mut res: Option<Rc<Node>> = None;
+        for (mono, c) in self.sorted_terms() {
+            let term = self.term_node(mono, c.abs())?;
+            res = Some(match (res, c.num < 0) {
+                (None, false) => term,
+                (None, true) => negate_leading(&term),
+                (Some(sum), false) => Node::binary(Add, sum, term),
+                (Some(sum), true) => Node::binary(Sub, sum, term),
+            });
+        }
+        Ok(res.unwrap_or_else(|| Node::num(0.0)))
+    }
+
+    fn by_power(&self, var: &str) -> BTreeMap<i32, Poly> {
+        self.powers(&Atom::Var(var.to_string()))
+    }
+
+    // Splits the terms by their power of `atom`, with `atom` taken out.
+    pub fn powers(&self, atom: &Atom) -> BTreeMap<i32, Poly> {
+        let mut powers: BTreeMap<i32, Poly> = BTreeMap::new();
+        for (mono, c) in &self.terms {
+            let rest = mono.iter().filter(|(atom_, _)| atom_ != atom).cloned().collect::<Monomial>();
+            let power = powers.entry(exponent(mono, atom)).or_insert_with(|| Poly { terms: BTreeMap::new(), nodes: self.nodes.clone() });
+            // Distinct monomials stay distinct with `atom` taken out.
+            power.terms.insert(rest, *c);
+        }
+        powers
+    }
+
+    // Fails when `var` occurs other than in nonnegative integer powers.
+    pub fn check_polynomial(&self, var: &str) -> Result<(), String> {
+        let atom = Atom::Var(var.to_string());
+        for mono in self.terms.keys() {
+            for (atom_, exp) in mono {
+                let inside = match atom_ {
+                    Atom::Other(_) => depends(&self.nodes[atom_], var),
+                    Atom::Var(_) => *atom_ == atom && *exp < 0,
+                };
+                if inside {
+                    return Err(format!("{} is not a polynomial in {}", self.to_node()?, var));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn var_name(var: &Rc<Node>) -> Result<String, String> {
+    match &**var {
+        Var { name, point: None, .. } => Ok(name.clone()),
+        _ => Err(format!("expected a variable, found {}", var)),
+    }
+}
+
+// Multiplies out products and integer powers, combining like terms.
+pub fn expand(node: &Rc<Node>) -> Result<Rc<Node>, String> {
+    match &**node {
+        Equation { lhs, rhs } => Ok(Node::equation(expand(lhs)?, expand(rhs)?)),
+        List { items } => Ok(Node::list(items.iter().map(expand).collect::<Result<_, _>>()?)),
+        _ => Poly::from_node(node)?.to_node(),
+    }
+}
+
+// Writes the expansion of `node` as a sum of powers of `var`, highest first, each
+// with its coefficient factored out.
+pub fn collect(node: &Rc<Node>, var: &Rc<Node>) -> Result<Rc<Node>, String> {
+    let name = var_name(var)?;
+    let mut res: Option<Rc<Node>> = None;
+    for (n, coeff) in Poly::from_node(node)?.by_power(&name).into_iter().rev() {
+        let power = match n {
+            0 => Node::num(1.0),
+            1 => var.clone(),
+            _ => Node::binary(Pow, var.clone(), Node::num(n as f32)),
+        };
+        let (coeff, negative) = match coeff.terms.len() {
+            1 if coeff.terms.values().next().unwrap().num < 0 => (coeff.neg().to_node()?, true),
+            _ => (coeff.to_node()?, false),
+        };
+        let term = simplify(&Node::binary(Mul, coeff, power));
+        res = Some(match (res, negative) {
+            (None, false) => term,
+            (None, true) => negate_leading(&term),
+            (Some(sum), false) => Node::binary(Add, sum, term),
+            (Some(sum), true) => Node::binary(Sub, sum, term),
+        });
+    }
+    Ok(res.unwrap_or_else(|| Node::num(0.0)))
+}
+
+pub fn degree(node: &Rc<Node>, var: &Rc<Node>) -> Result<Rc<Node>, String> {
+    let name = var_name(var)?;
+    let poly = Poly::from_node(node)?;
+    poly.check_polynomial(&name)?;
+    match poly.by_power(&name).keys().next_back() {
+        Some(n) => Ok(Node::num(*n as f32)),
+        None => Err("0 has no degree".to_string()),
+    }
+}
+
+// The coefficient of `var ^ n` in the expansion of `node`.
+pub fn coeff(node: &Rc<Node>, var: &Rc<Node>, n: &Rc<Node>) -> Result<Rc<Node>, String> {
+    let name = var_name(var)?;
+    let n = eval::<f64>(n, &Env::new())?;
+    if n.fract() != 0.0 {
+        return Err(format!("expected an integer power, found {}", n));
+    }
+    let poly = Poly::from_node(node)?;
+    poly.check_polynomial(&name)?;
+    poly.by_power(&name).get(&(n as i32)).map_or_else(|| Ok(Node::num(0.0)), Poly::to_node)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn x() -> Poly {
+        Poly::atom(Node::var("x", None), 1)
+    }
+
+    fn c(num: i128, den: i128) -> Poly {
+        Poly::constant(Rational::new(num, den).unwrap())
+    }
+
+    #[test]
+    fn like_terms_combine_and_zeros_vanish() {
+        let p = x().add(&c(1, 2)).unwrap().mul(&x().sub(&c(1, 2)).unwrap()).unwrap();
+        assert_eq!(p.to_node().unwrap().to_string(), "x ^ 2 - 1 / 4");
+        assert!(p.sub(&p).unwrap().is_zero());
+        assert_eq!(x().pow(0).unwrap().as_constant(), Some(Rational::int(1)));
+    }
+
+    #[test]
+    fn primitive_part_has_integer_coefficients_and_positive_lead() {
+        // `-x / 2 + 1 / 3`
+        let p = x().mul(&c(-1, 2)).unwrap().add(&c(1, 3)).unwrap();
+        assert_eq!(p.content(), Rational::new(-1, 6));
+        assert_eq!(p.primitive().unwrap().to_node().unwrap().to_string(), "3 * x - 2");
+    }
+
+    #[test]
+    fn exact_division() {
+        let p = x().pow(2).unwrap().sub(&c(1, 1)).unwrap();
+        let q = x().add(&c(1, 1)).unwrap();
+        assert_eq!(p.div_exact(&q).unwrap().unwrap().to_node().unwrap().to_string(), "x - 1");
+        assert!(q.div_exact(&p).unwrap().is_none());
+    }
+
+    #[test]
+    fn overflow_is_an_error() {
+        assert_eq!(c(1000, 1).pow(64).unwrap_err(), "coefficient overflow");
+        assert!(x().mul(&c(1, 7)).unwrap().add(&c(1, 3)).unwrap().pow(64).is_err());
+        let node = Node::binary(Pow, Node::binary(Add, Node::binary(Mul, Node::num(123456.0), Node::var("x", None)), Node::num(98765.0)), Node::num(20.0));
+        assert!(Poly::from_node(&node).is_err());
+    }
+
+    #[test]
+    fn inexact_coefficients_are_an_error() {
+        assert_eq!(c(16777216, 1).to_node().unwrap().to_string(), "16777216");
+        assert!(x().mul(&c(1879080904, 1)).unwrap().to_node().is_err());
+        assert!(x().mul(&c(1, 16777217)).unwrap().to_node().is_err());
+    }
+}
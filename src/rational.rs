@@ -0,0 +1,153 @@
+use std::fmt;
+use std::ops::Neg;
+
+pub fn gcd(a: i128, b: i128) -> i128 {
+    if b == 0 { a.abs() } else { gcd(b, a % b) }
+}
+
+pub fn overflow() -> String {
+    "coefficient overflow".to_string()
+}
+
+// An exact fraction in lowest terms with a positive denominator. Neither part
+// is ever `i128::MIN`, so negating and taking absolute values cannot overflow;
+// everything that can returns an error instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rational {
+    pub num: i128,
+    pub den: i128,
+}
+
+impl Rational {
+    pub fn new(num: i128, den: i128) -> Result<Rational, String> {
+        if den == 0 {
+            return Err("division by zero".to_string());
+        }
+        if num == i128::MIN || den == i128::MIN {
+            return Err(overflow());
+        }
+        let d = gcd(num, den) * den.signum();
+        Ok(Rational { num: num / d, den: den / d })
+    }
+
+    pub fn int(num: i128) -> Rational {
+        Rational { num, den: 1 }
+    }
+
+    // Constants are read as the decimal they print as, so `0.1` is one tenth
+    // rather than the binary fraction nearest to it.
+    pub fn from_f32(val: f32) -> Option<Rational> {
+        if !val.is_finite() {
+            return None;
+        }
+        let text = val.to_string();
+        let (int, frac) = text.split_once('.').unwrap_or((&text, ""));
+        let den = 10i128.checked_pow(frac.len() as u32)?;
+        let num = format!("{}{}", int, frac).parse::<i128>().ok()?;
+        Rational::new(num, den).ok()
+    }
+
+    // The `f32` that reads back as exactly this fraction, if there is one.
+    pub fn to_f32(self) -> Option<f32> {
+        let val = self.num as f32 / self.den as f32;
+        (Rational::from_f32(val) == Some(self)).then_some(val)
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.num == 0
+    }
+
+    pub fn is_integer(&self) -> bool {
+        self.den == 1
+    }
+
+    pub fn abs(&self) -> Rational {
+        Rational { num: self.num.abs(), den: self.den }
+    }
+
+    pub fn recip(&self) -> Result<Rational, String> {
+        Rational::new(self.den, self.num)
+    }
+
+    pub fn checked_add(&self, rhs: Rational) -> Result<Rational, String> {
+        let num = self.num.checked_mul(rhs.den).zip(rhs.num.checked_mul(self.den));
+        let num = num.and_then(|(a, b)| a.checked_add(b)).ok_or_else(overflow)?;
+        Rational::new(num, self.den.checked_mul(rhs.den).ok_or_else(overflow)?)
+    }
+
+    pub fn checked_mul(&self, rhs: Rational) -> Result<Rational, String> {
+        // Cancelling crosswise first keeps the products as small as they can be.
+        let (g, h) = (gcd(self.num, rhs.den), gcd(rhs.num, self.den));
+        let num = (self.num / g).checked_mul(rhs.num / h).ok_or_else(overflow)?;
+        Rational::new(num, (self.den / h).checked_mul(rhs.den / g).ok_or_else(overflow)?)
+    }
+
+    pub fn checked_div(&self, rhs: Rational) -> Result<Rational, String> {
+        self.checked_mul(rhs.recip()?)
+    }
+
+    pub fn checked_pow(&self, n: i32) -> Result<Rational, String> {
+        let base = if n < 0 { self.recip()? } else { *self };
+        let num = base.num.checked_pow(n.unsigned_abs()).ok_or_else(overflow)?;
+        Rational::new(num, base.den.checked_pow(n.unsigned_abs()).ok_or_else(overflow)?)
+    }
+}
+
+impl Neg for Rational {
+    type Output = Rational;
+    fn neg(self) -> Rational { Rational { num: -self.num, den: self.den } }
+}
+
+impl fmt::Display for Rational {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.den == 1 { write!(f, "{}", self.num) } else { write!(f, "{}/{}", self.num, self.den) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn q(num: i128, den: i128) -> Rational {
+        Rational::new(num, den).unwrap()
+    }
+
+    #[test]
+    fn lowest_terms_with_positive_denominator() {
+        assert_eq!(q(6, -4), Rational { num: -3, den: 2 });
+        assert_eq!(q(-6, -4), Rational { num: 3, den: 2 });
+        assert_eq!(q(0, -5), Rational::int(0));
+        assert!(Rational::new(1, 0).is_err());
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(q(1, 6).checked_add(q(1, 3)), Ok(q(1, 2)));
+        assert_eq!(q(1, 6).checked_add(-q(1, 3)), Ok(q(-1, 6)));
+        assert_eq!(q(2, 3).checked_mul(q(9, 4)), Ok(q(3, 2)));
+        assert_eq!(q(2, 3).checked_div(q(-4, 9)), Ok(q(-3, 2)));
+        assert_eq!(q(-2, 3).checked_pow(-3), Ok(q(-27, 8)));
+        assert!(q(1, 2).checked_div(Rational::int(0)).is_err());
+    }
+
+    #[test]
+    fn overflow_is_an_error() {
+        let big = Rational::int(i128::MAX / 2);
+        assert_eq!(big.checked_mul(Rational::int(4)), Err(overflow()));
+        assert_eq!(big.checked_add(big).and_then(|sum| sum.checked_add(big)), Err(overflow()));
+        assert_eq!(Rational::int(1000).checked_pow(64), Err(overflow()));
+        assert!(Rational::new(i128::MIN, 1).is_err());
+        // Cancelling crosswise avoids overflow where the result fits.
+        assert_eq!(q(i128::MAX, 3).checked_mul(q(3, i128::MAX)), Ok(Rational::int(1)));
+    }
+
+    #[test]
+    fn decimals_read_as_printed() {
+        assert_eq!(Rational::from_f32(0.1), Some(q(1, 10)));
+        assert_eq!(Rational::from_f32(-2.5), Some(q(-5, 2)));
+        assert_eq!(q(1, 10).to_f32(), Some(0.1));
+        assert_eq!(q(1, 3).to_f32(), None);
+        assert_eq!(Rational::int(16777216).to_f32(), Some(16777216.0));
+        assert_eq!(Rational::int(1879080904).to_f32(), None);
+    }
+}
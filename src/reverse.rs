@@ -0,0 +1,143 @@
+use std::cell::RefCell;
+use std::ops::{Add, Sub, Mul, Div, Neg};
+use std::rc::Rc;
+
+use crate::eval::*;
+use crate::node::*;
+
+// Each tape entry records up to two parents with the partial derivative of the
+// entry with respect to each of them. Unused slots have a zero partial.
+type Entry = [(usize, f64); 2];
+
+thread_local! {
+    static TAPE: RefCell<Vec<Entry>> = const { RefCell::new(Vec::new()) };
+}
+
+// A value recorded on the tape during the forward sweep.
+#[derive(Debug, Clone, Copy)]
+pub struct Adj {
+    index: usize,
+    val: f64,
+}
+
+impl Adj {
+    fn record(val: f64, entry: Entry) -> Adj {
+        let index = TAPE.with(|tape| {
+            let mut tape = tape.borrow_mut();
+            tape.push(entry);
+            tape.len() - 1
+        });
+        Adj { index, val }
+    }
+
+    fn unary(self, val: f64, partial: f64) -> Adj {
+        Adj::record(val, [(self.index, partial), (0, 0.0)])
+    }
+
+    fn binary(self, rhs: Adj, val: f64, partial_lhs: f64, partial_rhs: f64) -> Adj {
+        Adj::record(val, [(self.index, partial_lhs), (rhs.index, partial_rhs)])
+    }
+}
+
+impl Add for Adj {
+    type Output = Adj;
+    fn add(self, rhs: Adj) -> Adj { self.binary(rhs, self.val + rhs.val, 1.0, 1.0) }
+}
+
+impl Sub for Adj {
+    type Output = Adj;
+    fn sub(self, rhs: Adj) -> Adj { self.binary(rhs, self.val - rhs.val, 1.0, -1.0) }
+}
+
+impl Mul for Adj {
+    type Output = Adj;
+    fn mul(self, rhs: Adj) -> Adj { self.binary(rhs, self.val * rhs.val, rhs.val, self.val) }
+}
+
+impl Div for Adj {
+    type Output = Adj;
+    fn div(self, rhs: Adj) -> Adj {
+        self.binary(rhs, self.val / rhs.val, 1.0 / rhs.val, -self.val / (rhs.val * rhs.val))
+    }
+}
+
+impl Neg for Adj {
+    type Output = Adj;
+    fn neg(self) -> Adj { self.unary(-self.val, -1.0) }
+}
+
+impl Scalar for Adj {
+    fn constant(val: f64) -> Self { Adj::record(val, [(0, 0.0), (0, 0.0)]) }
+    fn imaginary() -> Option<Self> { None }
+    fn sin(self) -> Self { self.unary(self.val.sin(), self.val.cos()) }
+    fn cos(self) -> Self { self.unary(self.val.cos(), -self.val.sin()) }
+    fn tan(self) -> Self { self.unary(self.val.tan(), 1.0 / (self.val.cos() * self.val.cos())) }
+    fn exp(self) -> Self { self.unary(self.val.exp(), self.val.exp()) }
+    fn log(self) -> Self { self.unary(self.val.ln(), 1.0 / self.val) }
+    fn asin(self) -> Self { self.unary(self.val.asin(), 1.0 / (1.0 - self.val * self.val).sqrt()) }
+    fn pow(self, exponent: Self) -> Self {
+        let val = self.val.powf(exponent.val);
+        let partial_base = exponent.val * self.val.powf(exponent.val - 1.0);
+        // The exponent only contributes where the logarithm of the base exists,
+        // which keeps constant exponents of negative bases from producing NaN.
+        let partial_exponent = if self.val > 0.0 { val * self.val.ln() } else { 0.0 };
+        self.binary(exponent, val, partial_base, partial_exponent)
+    }
+    fn re(self) -> Self { self }
+    fn im(self) -> Self { Adj::constant(0.0) }
+    fn conj(self) -> Self { self }
+    fn arg(self) -> Self { self.unary(self.val.arg(), 0.0) }
+    fn abs(self) -> Self { self.unary(self.val.abs(), self.val.signum()) }
+}
+
+// Evaluates `node` at `point` in one forward sweep and returns its value together
+// with the partial derivatives with respect to `vars` from one backward sweep.
+pub fn gradient(node: &Rc<Node>, vars: &[Rc<Node>], point: &[f64]) -> Result<(f64, Vec<f64>), String> {
+    TAPE.with(|tape| tape.borrow_mut().clear());
+
+    let mut env = Env::new();
+    let mut inputs = Vec::new();
+    for (var, val) in vars.iter().zip(point) {
+        match &**var {
+            Node::Var { name, .. } => {
+                let input = Adj::constant(*val);
+                env.insert(name.clone(), input);
+                inputs.push(input);
+            },
+            _ => return Err("expected a variable".to_string()),
+        }
+    }
+    let output = eval(node, &env)?;
+
+    let tape = TAPE.with(|tape| tape.take());
+    let mut adjoint = vec![0.0; tape.len()];
+    adjoint[output.index] = 1.0;
+    for index in (0..tape.len()).rev() {
+        for (parent, partial) in tape[index] {
+            if partial != 0.0 {
+                adjoint[parent] += adjoint[index] * partial;
+            }
+        }
+    }
+
+    Ok((output.val, inputs.iter().map(|input| adjoint[input.index]).collect()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    #[test]
+    fn gradient_matches_symbolic_derivatives() {
+        let node = parse("var x, y; x ^ y * sin(x * y) + log(x) / y");
+        let vars = ["x", "y"].map(|name| Node::var(name, None));
+        let (value, grad) = gradient(&node, &vars, &[2.0, 3.0]).unwrap();
+        let at = |node: &Rc<Node>| eval::<f64>(node, &[("x".to_string(), 2.0), ("y".to_string(), 3.0)].into()).unwrap();
+        assert!((value - at(&node)).abs() < 1e-12);
+        for (partial, var) in grad.iter().zip(&vars) {
+            let exact = at(&Node::dif(&node, var));
+            assert!((partial - exact).abs() <= 1e-12 * exact.abs().max(1.0), "{} != {}", partial, exact);
+        }
+    }
+}
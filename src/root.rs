@@ -0,0 +1,167 @@
+use crate::bytecode::Program;
+
+#[derive(Debug)]
+pub struct Root {
+    pub x: f64,
+    pub residual: f64,
+    pub iterations: usize,
+}
+
+fn converged(step: f64, x: f64, tolerance: f64) -> bool {
+    step.abs() <= tolerance * x.abs().max(1.0)
+}
+
+// Newton-Raphson iteration from `guess`, using the exact derivative `df`. When a
+// bracket is given the iterates must stay inside it.
+pub fn newton(f: &Program, df: &Program, guess: f64, bracket: Option<(f64, f64)>, tolerance: f64, iterations: usize) -> Result<Root, String> {
+    let mut x = guess;
+    for i in 1..=iterations {
+        let fx = f.at(&[x]);
+        if fx == 0.0 {
+            return Ok(Root { x, residual: 0.0, iterations: i - 1 });
+        }
+        let dfx = df.at(&[x]);
+        if !fx.is_finite() || !dfx.is_finite() {
+            return Err(format!("f or its derivative is undefined at x = {} (iteration {})", x, i));
+        }
+        if dfx == 0.0 {
+            return Err(format!("the derivative vanished at x = {} (iteration {})", x, i));
+        }
+        let step = fx / dfx;
+        x -= step;
+        if !x.is_finite() {
+            return Err(format!("diverged at iteration {}", i));
+        }
+        if let Some((a, b)) = bracket {
+            if x < a || x > b {
+                return Err(format!("left the bracket [{}, {}] at x = {} (iteration {})", a, b, x, i));
+            }
+        }
+        if converged(step, x, tolerance) {
+            return Ok(Root { x, residual: f.at(&[x]), iterations: i });
+        }
+    }
+    Err(format!("did not converge after {} iterations; last x = {}, f(x) = {}", iterations, x, f.at(&[x])))
+}
+
+// Brent's method on a bracket where `f` changes sign: inverse quadratic
+// interpolation or secant steps, falling back to bisection whenever those would
+// not shrink the bracket fast enough.
+pub fn brent(f: &Program, (a, b): (f64, f64), tolerance: f64, iterations: usize) -> Result<Root, String> {
+    let (mut a, mut b) = (a, b);
+    let (mut fa, mut fb) = (f.at(&[a]), f.at(&[b]));
+    if !fa.is_finite() || !fb.is_finite() {
+        return Err(format!("f is undefined at an end of the bracket [{}, {}]", a, b));
+    }
+    if fa * fb > 0.0 {
+        return Err(format!("f({}) = {} and f({}) = {} have the same sign", a, fa, b, fb));
+    }
+    let (mut c, mut fc) = (a, fa);
+    let (mut d, mut e) = (b - a, b - a);
+    for i in 1..=iterations {
+        if fb * fc > 0.0 {
+            c = a;
+            fc = fa;
+            d = b - a;
+            e = d;
+        }
+        if fc.abs() < fb.abs() {
+            a = b;
+            b = c;
+            c = a;
+            fa = fb;
+            fb = fc;
+            fc = fa;
+        }
+        let tol = 2.0 * f64::EPSILON * b.abs() + 0.5 * tolerance * b.abs().max(1.0);
+        let m = 0.5 * (c - b);
+        if m.abs() <= tol || fb == 0.0 {
+            return Ok(Root { x: b, residual: fb, iterations: i });
+        }
+        if e.abs() >= tol && fa.abs() > fb.abs() {
+            let s = fb / fa;
+            let (mut p, mut q) = if a == c {
+                (2.0 * m * s, 1.0 - s)
+            } else {
+                let q = fa / fc;
+                let r = fb / fc;
+                (s * (2.0 * m * q * (q - r) - (b - a) * (r - 1.0)), (q - 1.0) * (r - 1.0) * (s - 1.0))
+            };
+            if p > 0.0 {
+                q = -q;
+            } else {
+                p = -p;
+            }
+            if 2.0 * p < (3.0 * m * q - (tol * q).abs()).min((e * q).abs()) {
+                e = d;
+                d = p / q;
+            } else {
+                d = m;
+                e = m;
+            }
+        } else {
+            d = m;
+            e = m;
+        }
+        a = b;
+        fa = fb;
+        b += if d.abs() > tol { d } else { tol.copysign(m) };
+        fb = f.at(&[b]);
+        if !fb.is_finite() {
+            return Err(format!("f is undefined at x = {} inside the bracket", b));
+        }
+    }
+    Err(format!("did not converge after {} iterations; last x = {}, f(x) = {}", iterations, b, fb))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::Node;
+    use crate::parser::parse;
+
+    fn compile(src: &str) -> (Program, Program) {
+        let node = parse(&format!("var x; {}", src));
+        let x = ["x".to_string()];
+        (Program::compile(&node, &x).unwrap(), Program::compile(&Node::dif(&node, &Node::var("x", None)), &x).unwrap())
+    }
+
+    #[test]
+    fn newton_converges_quadratically() {
+        let (f, df) = compile("x ^ 2 - 2");
+        let root = newton(&f, &df, 1.0, None, 1e-12, 100).unwrap();
+        assert!((root.x - 2f64.sqrt()).abs() < 1e-12);
+        assert!(root.residual.abs() < 1e-12);
+        assert!(root.iterations <= 6);
+    }
+
+    #[test]
+    fn newton_failures() {
+        let (f, df) = compile("x ^ 2 - 2");
+        assert!(newton(&f, &df, 0.0, None, 1e-12, 100).unwrap_err().contains("derivative vanished"));
+        assert!(newton(&f, &df, 1.0, None, 1e-12, 2).unwrap_err().contains("did not converge after 2"));
+        // From 2 the first step lands on 1.5, outside the bracket.
+        assert!(newton(&f, &df, 2.0, Some((1.6, 3.0)), 1e-12, 100).unwrap_err().contains("left the bracket"));
+        let (f, df) = compile("x ^ (1 / 3)");
+        assert!(newton(&f, &df, 1.0, None, 1e-12, 100).is_err());
+    }
+
+    #[test]
+    fn brent_converges() {
+        let (f, _) = compile("cos(x) - x");
+        let root = brent(&f, (0.0, 1.0), 1e-12, 100).unwrap();
+        assert!((root.x - 0.7390851332151607).abs() < 1e-10);
+        let (f, _) = compile("x ^ 3");
+        assert!(brent(&f, (-1.0, 2.0), 1e-12, 200).unwrap().x.abs() < 1e-6);
+    }
+
+    #[test]
+    fn brent_failures() {
+        let (f, _) = compile("x ^ 2 + 1");
+        assert!(brent(&f, (-1.0, 1.0), 1e-12, 100).unwrap_err().contains("same sign"));
+        let (f, _) = compile("cos(x) - x");
+        assert!(brent(&f, (0.0, 1.0), 1e-12, 2).unwrap_err().contains("did not converge after 2"));
+        let (f, _) = compile("log(x)");
+        assert!(brent(&f, (0.0, 2.0), 1e-12, 100).unwrap_err().contains("undefined"));
+    }
+}
@@ -0,0 +1,371 @@
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use crate::node::*;
+
+use BKind::*;
+use UKind::*;
+use Node::*;
+
+fn num_of(node: &Node) -> Option<f32> {
+    match node {
+        Num { val } => Some(*val),
+        _ => None,
+    }
+}
+
+fn is_num(node: &Node, val: f32) -> bool {
+    num_of(node) == Some(val)
+}
+
+// Folds `val` into a constant only when `Node::num` can hold it exactly, so that
+// `1 / 3` stays a quotient instead of losing precision.
+fn exact(val: f64) -> Option<Rc<Node>> {
+    if val.is_finite() && (val as f32) as f64 == val {
+        Some(Node::num(if val == 0.0 { 0.0 } else { val as f32 }))
+    } else {
+        None
+    }
+}
+
+fn apply_binary(kind: BKind, lhs: f32, rhs: f32) -> f64 {
+    let (lhs, rhs) = (lhs as f64, rhs as f64);
+    match kind {
+        Add => lhs + rhs,
+        Sub => lhs - rhs,
+        Mul => lhs * rhs,
+        Div => lhs / rhs,
+        Pow => lhs.powf(rhs),
+    }
+}
+
+fn fold_binary(kind: BKind, lhs: f32, rhs: f32) -> Option<Rc<Node>> {
+    exact(apply_binary(kind, lhs, rhs))
+}
+
+fn fold_unary(kind: UKind, operand: f32) -> Option<Rc<Node>> {
+    let operand = operand as f64;
+    exact(match kind {
+        Neg => -operand,
+        Sin => operand.sin(),
+        Cos => operand.cos(),
+        Tan => operand.tan(),
+        Exp => operand.exp(),
+        Log => operand.ln(),
+        Asin => operand.asin(),
+        Re | Conj => operand,
+        Im => 0.0,
+        Arg => f64::atan2(0.0, operand),
+        Abs => operand.abs(),
+    })
+}
+
+fn is_const(node: &Node, kind: CKind) -> bool {
+    matches!(node, Const { kind: kind_ } if *kind_ == kind)
+}
+
+// `inf` or `-inf`, for which identities such as `0 * x = 0` do not hold.
+fn is_infinite(node: &Node) -> bool {
+    match node {
+        Const { kind: CKind::Inf } => true,
+        UnaryOperator { kind: Neg, operand } => is_infinite(operand),
+        _ => false,
+    }
+}
+
+// `node / pi`, for constant multiples of `pi`.
+fn pi_multiple(node: &Node) -> Option<f64> {
+    match node {
+        Const { kind: CKind::Pi } => Some(1.0),
+        BinaryOperator { kind: Mul, lhs, rhs } => match (num_of(lhs), num_of(rhs)) {
+            (Some(val), _) => Some(val as f64 * pi_multiple(rhs)?),
+            (_, Some(val)) => Some(pi_multiple(lhs)? * val as f64),
+            _ => None,
+        },
+        BinaryOperator { kind: Div, lhs, rhs } => Some(pi_multiple(lhs)? / num_of(rhs)? as f64),
+        UnaryOperator { kind: Neg, operand } => Some(-pi_multiple(operand)?),
+        _ => None,
+    }
+}
+
+// Whether `node` is free of `i`. Variables are real, and so are the functions
+// SigMa has of real arguments within their domains.
+fn is_real(node: &Rc<Node>) -> bool {
+    fn visit(node: &Rc<Node>, seen: &mut HashSet<*const Node>) -> bool {
+        if !seen.insert(Rc::as_ptr(node)) {
+            return true;
+        }
+        match &**node {
+            BinaryOperator { lhs, rhs, .. } => visit(lhs, seen) && visit(rhs, seen),
+            UnaryOperator { kind: Re | Im | Arg | Abs, .. } => true,
+            UnaryOperator { operand, .. } => visit(operand, seen),
+            Var { point: Some(point), .. } => visit(point, seen),
+            Const { kind: CKind::I } => false,
+            Var { point: None, .. } | Num { .. } | Const { .. } | Derivative { .. } => true,
+            List { items } => items.iter().all(|item| visit(item, seen)),
+            Equation { .. } | Unsolved { .. } => false,
+        }
+    }
+    visit(node, &mut HashSet::new())
+}
+
+// 1 for `i` and -1 for `-i`.
+fn sign_of_i(node: &Node) -> Option<f32> {
+    match node {
+        Const { kind: CKind::I } => Some(1.0),
+        UnaryOperator { kind: Neg, operand } if is_const(operand, CKind::I) => Some(-1.0),
+        _ => None,
+    }
+}
+
+// `i ^ n` for an integer `n`.
+fn power_of_i(n: f32) -> Option<Rc<Node>> {
+    if n.fract() != 0.0 {
+        return None;
+    }
+    Some(match (n as i64).rem_euclid(4) {
+        0 => Node::num(1.0),
+        1 => Node::constant(CKind::I),
+        2 => Node::num(-1.0),
+        _ => Node::unary(Neg, Node::constant(CKind::I)),
+    })
+}
+
+// `exp(i * t) = cos(t) + i * sin(t)` when both are exact, as for `exp(i * pi)`.
+fn polar(operand: &Node) -> Option<Rc<Node>> {
+    let angle = match operand {
+        BinaryOperator { kind: Mul, lhs, rhs } if is_const(lhs, CKind::I) => rhs,
+        BinaryOperator { kind: Mul, lhs, rhs } if is_const(rhs, CKind::I) => lhs,
+        _ => return None,
+    };
+    let cos = special_angle(Cos, angle)?;
+    let sin = special_angle(Sin, angle)?;
+    Some(binary(Add, cos, binary(Mul, sin, Node::constant(CKind::I))))
+}
+
+// Sines, cosines and tangents of multiples of `pi / 6` whose values are exact.
+fn special_angle(kind: UKind, operand: &Node) -> Option<Rc<Node>> {
+    const SINES: [Option<f32>; 12] = [Some(0.0), Some(0.5), None, Some(1.0), None, Some(0.5), Some(0.0), Some(-0.5), None, Some(-1.0), None, Some(-0.5)];
+    let k = pi_multiple(operand)? * 6.0;
+    if k.fract() != 0.0 {
+        return None;
+    }
+    let k = (k as i64).rem_euclid(12) as usize;
+    let val = match kind {
+        Sin => SINES[k]?,
+        Cos => SINES[(k + 3) % 12]?,
+        Tan if k.is_multiple_of(6) => 0.0,
+        _ => return None,
+    };
+    Some(Node::num(val))
+}
+
+// The positive form of a negated term: `-a`, a negative constant, or a product or
+// quotient with a negative constant in front.
+fn negated(node: &Rc<Node>) -> Option<Rc<Node>> {
+    match &**node {
+        UnaryOperator { kind: Neg, operand } => Some(operand.clone()),
+        Num { val } if *val < 0.0 => Some(Node::num(-val)),
+        BinaryOperator { kind: kind @ (Mul | Div), lhs, rhs } => match &**lhs {
+            Num { val } if *val < 0.0 => Some(binary(*kind, Node::num(-val), rhs.clone())),
+            UnaryOperator { kind: Neg, operand } => Some(binary(*kind, operand.clone(), rhs.clone())),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// Indeterminate forms such as `0 / 0`, `0 ^ 0` and `inf - inf` have no value,
+// so they are left as they are.
+fn is_indeterminate(kind: BKind, lhs: &Node, rhs: &Node) -> bool {
+    match (num_of(lhs), num_of(rhs)) {
+        (Some(a), Some(b)) => !apply_binary(kind, a, b).is_finite() || (kind == Pow && a == 0.0 && b == 0.0),
+        _ => is_infinite(lhs) || is_infinite(rhs),
+    }
+}
+
+// Whether a simplified `node` still contains a binary operation that
+// `simplify` left alone because it is indeterminate.
+pub fn has_indeterminate(node: &Rc<Node>) -> bool {
+    fn visit(node: &Rc<Node>, seen: &mut HashSet<*const Node>) -> bool {
+        if !seen.insert(Rc::as_ptr(node)) {
+            return false;
+        }
+        match &**node {
+            BinaryOperator { kind, lhs, rhs } => is_indeterminate(*kind, lhs, rhs) || visit(lhs, seen) || visit(rhs, seen),
+            UnaryOperator { operand, .. } => visit(operand, seen),
+            Var { point: Some(point), .. } => visit(point, seen),
+            List { items } => items.iter().any(|item| visit(item, seen)),
+            Equation { lhs, rhs } => visit(lhs, seen) || visit(rhs, seen),
+            Unsolved { equation, .. } => visit(equation, seen),
+            Var { point: None, .. } | Num { .. } | Const { .. } | Derivative { .. } => false,
+        }
+    }
+    visit(node, &mut HashSet::new())
+}
+
+fn binary(kind: BKind, lhs: Rc<Node>, rhs: Rc<Node>) -> Rc<Node> {
+    let indeterminate = is_indeterminate(kind, &lhs, &rhs);
+    if let (Some(lhs), Some(rhs)) = (num_of(&lhs), num_of(&rhs)) {
+        if let Some(res) = fold_binary(kind, lhs, rhs).filter(|_| !indeterminate) {
+            return res;
+        }
+    }
+    match kind {
+        Sub | Mul | Div | Pow if indeterminate => Node::binary(kind, lhs, rhs),
+        Add if is_num(&lhs, 0.0) => rhs,
+        Add if is_num(&rhs, 0.0) => lhs,
+        Add => match negated(&rhs) {
+            Some(rhs) => binary(Sub, lhs, rhs),
+            None => Node::binary(Add, lhs, rhs),
+        },
+        Sub if is_num(&rhs, 0.0) => lhs,
+        Sub if is_num(&lhs, 0.0) => unary(Neg, rhs),
+        Sub if Node::equiv(&lhs, &rhs) => Node::num(0.0),
+        Sub => match negated(&rhs) {
+            Some(rhs) => binary(Add, lhs, rhs),
+            None => Node::binary(Sub, lhs, rhs),
+        },
+        Mul if is_num(&lhs, 0.0) || is_num(&rhs, 0.0) => Node::num(0.0),
+        Mul if is_num(&lhs, 1.0) => rhs,
+        Mul if is_num(&rhs, 1.0) => lhs,
+        Mul if is_num(&lhs, -1.0) => unary(Neg, rhs),
+        Mul if is_num(&rhs, -1.0) => unary(Neg, lhs),
+        Div if is_num(&lhs, 0.0) => Node::num(0.0),
+        Div if is_num(&rhs, 1.0) => lhs,
+        Pow if is_num(&rhs, 0.0) || is_num(&lhs, 1.0) => Node::num(1.0),
+        Pow if is_num(&rhs, 1.0) => lhs,
+        Mul if sign_of_i(&lhs).is_some() && sign_of_i(&rhs).is_some() => {
+            Node::num(-sign_of_i(&lhs).unwrap() * sign_of_i(&rhs).unwrap())
+        },
+        Pow if is_const(&lhs, CKind::I) => match num_of(&rhs).and_then(power_of_i) {
+            Some(res) => res,
+            None => Node::binary(Pow, lhs, rhs),
+        },
+        _ => Node::binary(kind, lhs, rhs),
+    }
+}
+
+fn unary(kind: UKind, operand: Rc<Node>) -> Rc<Node> {
+    if let Some(val) = num_of(&operand) {
+        if let Some(res) = fold_unary(kind, val) {
+            return res;
+        }
+    }
+    if let Some(res) = special_angle(kind, &operand) {
+        return res;
+    }
+    if kind == Exp {
+        if let Some(res) = polar(&operand) {
+            return res;
+        }
+    }
+    match (kind, &*operand) {
+        (Neg, UnaryOperator { kind: Neg, operand }) => operand.clone(),
+        (Log, Const { kind: CKind::E }) => Node::num(1.0),
+        (Log, UnaryOperator { kind: Exp, operand }) => operand.clone(),
+        (Log, BinaryOperator { kind: Pow, lhs, rhs }) if is_const(lhs, CKind::E) => rhs.clone(),
+        (Exp, Num { val }) if *val == 1.0 => Node::constant(CKind::E),
+        (Asin, Num { val }) if val.abs() == 1.0 || val.abs() == 0.5 => {
+            let angle = binary(Div, Node::constant(CKind::Pi), Node::num(if val.abs() == 1.0 { 2.0 } else { 6.0 }));
+            if *val < 0.0 { unary(Neg, angle) } else { angle }
+        },
+        (Neg, BinaryOperator { kind: Sub, lhs, rhs }) => binary(Sub, rhs.clone(), lhs.clone()),
+        (Re | Conj, _) if is_real(&operand) => operand,
+        (Im, _) if is_real(&operand) => Node::num(0.0),
+        (Re, Const { kind: CKind::I }) => Node::num(0.0),
+        (Im, Const { kind: CKind::I }) | (Abs, Const { kind: CKind::I }) => Node::num(1.0),
+        (Conj, Const { kind: CKind::I }) => unary(Neg, operand),
+        (Arg, Const { kind: CKind::I }) => binary(Div, Node::constant(CKind::Pi), Node::num(2.0)),
+        (Arg, Num { val }) if *val < 0.0 => Node::constant(CKind::Pi),
+        (Conj, UnaryOperator { kind: Conj, operand }) => operand.clone(),
+        (Re | Abs, UnaryOperator { kind: Conj, operand }) => unary(kind, operand.clone()),
+        (Im, UnaryOperator { kind: Conj, operand }) => unary(Neg, unary(Im, operand.clone())),
+        // Both parts are linear over the reals, so `re(x + i * w)` is `x`.
+        (Re | Im, BinaryOperator { kind: op @ (Add | Sub), lhs, rhs }) => {
+            binary(*op, unary(kind, lhs.clone()), unary(kind, rhs.clone()))
+        },
+        (Re | Im, BinaryOperator { kind: op @ (Mul | Div), lhs, rhs }) if is_real(rhs) => {
+            binary(*op, unary(kind, lhs.clone()), rhs.clone())
+        },
+        (Re | Im, BinaryOperator { kind: Mul, lhs, rhs }) if is_real(lhs) => {
+            binary(Mul, lhs.clone(), unary(kind, rhs.clone()))
+        },
+        (Re | Im, UnaryOperator { kind: Neg, operand }) => unary(Neg, unary(kind, operand.clone())),
+        (Abs, UnaryOperator { kind: Abs | Neg, operand }) => unary(Abs, operand.clone()),
+        _ => Node::unary(kind, operand),
+    }
+}
+
+// Folds constants and removes identities such as `x * 1`, `x + 0` and `x ^ 1`,
+// which `Node::dif` produces in large numbers. Defined variables are replaced by
+// their simplified definitions.
+pub fn simplify(node: &Rc<Node>) -> Rc<Node> {
+    simplify_shared(node, &mut HashMap::new())
+}
+
+fn simplify_shared(node: &Rc<Node>, memo: &mut HashMap<*const Node, Rc<Node>>) -> Rc<Node> {
+    if let Some(res) = memo.get(&Rc::as_ptr(node)) {
+        return res.clone();
+    }
+    let res = match &**node {
+        BinaryOperator { kind, lhs, rhs } => {
+            binary(*kind, simplify_shared(lhs, memo), simplify_shared(rhs, memo))
+        },
+        UnaryOperator { kind, operand } => unary(*kind, simplify_shared(operand, memo)),
+        Var { point: Some(point), .. } => simplify_shared(point, memo),
+        Var { point: None, .. } | Num { .. } | Const { .. } | Derivative { .. } => node.clone(),
+        List { items } => Node::list(items.iter().map(|item| simplify_shared(item, memo)).collect()),
+        Equation { lhs, rhs } => Node::equation(simplify_shared(lhs, memo), simplify_shared(rhs, memo)),
+        Unsolved { equation, var } => Node::unsolved(simplify_shared(equation, memo), var.clone()),
+    };
+    memo.insert(Rc::as_ptr(node), res.clone());
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    fn inf() -> Rc<Node> {
+        Node::constant(CKind::Inf)
+    }
+
+    #[test]
+    fn identities() {
+        let x = Node::var("x", None);
+        assert_eq!(simplify(&Node::binary(Sub, x.clone(), x.clone())).to_string(), "0");
+        assert_eq!(simplify(&Node::binary(Mul, Node::num(0.0), x.clone())).to_string(), "0");
+        assert_eq!(simplify(&Node::binary(Pow, x.clone(), Node::num(0.0))).to_string(), "1");
+        assert_eq!(simplify(&Node::binary(Div, Node::num(3.0), Node::num(4.0))).to_string(), "0.75");
+        assert_eq!(simplify(&Node::unary(Sin, Node::constant(CKind::Pi))).to_string(), "0");
+    }
+
+    #[test]
+    fn indeterminate_forms_stay() {
+        let (zero, one) = (Node::num(0.0), Node::num(1.0));
+        let cases = [
+            (Node::binary(Sub, inf(), inf()), "inf - inf"),
+            (Node::binary(Mul, zero.clone(), inf()), "0 * inf"),
+            (Node::binary(Div, zero.clone(), zero.clone()), "0 / 0"),
+            (Node::binary(Pow, inf(), zero.clone()), "inf ^ 0"),
+            (Node::binary(Pow, zero.clone(), zero.clone()), "0 ^ 0"),
+            (Node::binary(Pow, one, inf()), "1 ^ inf"),
+        ];
+        for (node, printed) in cases {
+            assert_eq!(simplify(&node).to_string(), printed);
+        }
+    }
+
+    #[test]
+    fn real_and_imaginary_parts() {
+        let parts = |src: &str| simplify(&parse(&format!("var x, w; {}", src))).to_string();
+        assert_eq!(parts("re(x + i * w)"), "x");
+        assert_eq!(parts("im(x + i * w)"), "w");
+        assert_eq!(parts("re(2 * (x - i))"), "2 * x");
+        assert_eq!(parts("im(3 * (x - 2 * i) / w)"), "-6 / w");
+        assert_eq!(parts("re(-(i * x))"), "0");
+        assert_eq!(parts("im(conj(x + i))"), "-1");
+    }
+}
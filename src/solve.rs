@@ -0,0 +1,544 @@
+use std::rc::Rc;
+
+use crate::eval::*;
+use crate::factor::cancel;
+use crate::node::*;
+use crate::poly::{self, depends, is_zero};
+use crate::rational::gcd;
+use crate::simplify::simplify;
+
+use BKind::*;
+use UKind::*;
+use Node::*;
+
+// Rational roots are only searched for when the constant and leading
+// coefficients have few enough divisors to try them all.
+const MAX_DIVISOR_SEARCH: i128 = 1_000_000_000_000;
+
+fn value(node: &Rc<Node>) -> Option<f64> {
+    eval::<f64>(node, &Env::new()).ok()
+}
+
+fn divisors(n: i128) -> Vec<i128> {
+    let n = n.abs();
+    let mut small = Vec::new();
+    let mut large = Vec::new();
+    let mut d = 1;
+    while d * d <= n {
+        if n % d == 0 {
+            small.push(d);
+            if d * d != n {
+                large.push(n / d);
+            }
+        }
+        d += 1;
+    }
+    small.extend(large.into_iter().rev());
+    small
+}
+
+// Scales constant coefficients to integers. Constants are f32, so they are
+// binary fractions and a power of two always clears their denominators.
+fn integers(coeffs: &[Rc<Node>]) -> Option<Vec<i128>> {
+    let vals = coeffs.iter().map(|coeff| match **coeff {
+        Num { val } => Some(val as f64),
+        _ => None,
+    }).collect::<Option<Vec<f64>>>()?;
+    let scale = (0..=30).map(|k| (1u64 << k) as f64).find(|scale| vals.iter().all(|val| (val * scale).fract() == 0.0))?;
+    let ints = vals.iter().map(|val| val * scale).collect::<Vec<f64>>();
+    if ints.iter().any(|val| val.abs() >= 2f64.powi(53)) {
+        return None;
+    }
+    let ints = ints.iter().map(|val| *val as i128).collect::<Vec<i128>>();
+    let content = ints.iter().fold(0, |acc, val| gcd(acc, *val));
+    Some(ints.iter().map(|val| val / content).collect())
+}
+
+// `q^n * a(p / q)`, which is zero exactly when `p / q` is a root.
+fn is_root(a: &[i128], p: i128, q: i128) -> bool {
+    let n = a.len() as u32 - 1;
+    let mut sum: i128 = 0;
+    for (i, coeff) in a.iter().enumerate() {
+        let term = p.checked_pow(i as u32)
+            .and_then(|pi| q.checked_pow(n - i as u32).and_then(|qi| pi.checked_mul(qi)))
+            .and_then(|term| term.checked_mul(*coeff));
+        match term.and_then(|term| sum.checked_add(term)) {
+            Some(next) => sum = next,
+            None => return false,
+        }
+    }
+    sum == 0
+}
+
+// Divides `a` by `q x - p`, which leaves integer coefficients when `p / q` is a
+// root in lowest terms.
+fn deflate(a: &[i128], p: i128, q: i128) -> Vec<i128> {
+    let n = a.len() - 1;
+    let mut b = vec![0; n];
+    b[n - 1] = a[n] / q;
+    for k in (1..n).rev() {
+        b[k - 1] = (a[k] + p * b[k]) / q;
+    }
+    b
+}
+
+// Splits off every rational root `p / q`, where `p` divides the constant
+// coefficient and `q` the leading one.
+fn rational_roots(mut a: Vec<i128>) -> (Vec<(i128, i128)>, Vec<i128>) {
+    let mut roots = Vec::new();
+    'deflate: while a.len() > 1 {
+        let (first, last) = (a[0], a[a.len() - 1]);
+        if first.abs() > MAX_DIVISOR_SEARCH || last.abs() > MAX_DIVISOR_SEARCH {
+            break;
+        }
+        for p in divisors(first) {
+            for q in divisors(last) {
+                if gcd(p, q) != 1 || p >= 1 << 24 || q >= 1 << 24 {
+                    continue;
+                }
+                for p in [p, -p] {
+                    if is_root(&a, p, q) {
+                        roots.push((p, q));
+                        a = deflate(&a, p, q);
+                        continue 'deflate;
+                    }
+                }
+            }
+        }
+        break;
+    }
+    (roots, a)
+}
+
+// The square root of `node`, with the largest square that divides its
+// integer constant factor taken out, as in `8 ^ 0.5 = 2 * 2 ^ 0.5`.
+fn sqrt(node: &Rc<Node>) -> Rc<Node> {
+    let (c, rest) = match &**node {
+        Num { val } => (*val, None),
+        BinaryOperator { kind: Mul, lhs, rhs } => match **lhs {
+            Num { val } => (val, Some(rhs.clone())),
+            _ => (1.0, Some(node.clone())),
+        },
+        _ => (1.0, Some(node.clone())),
+    };
+    if c.fract() != 0.0 || c <= 0.0 || c >= (1 << 24) as f32 {
+        return Node::binary(Pow, node.clone(), Node::num(0.5));
+    }
+    let c = c as i64;
+    let k = (1..).take_while(|k| k * k <= c).filter(|k| c % (k * k) == 0).last().unwrap_or(1);
+    let m = Node::num((c / (k * k)) as f32);
+    let radicand = match rest {
+        Some(rest) => simplify(&Node::binary(Mul, m, rest)),
+        None => m,
+    };
+    let root = match *radicand {
+        Num { val: 1.0 } => radicand.clone(),
+        _ => Node::binary(Pow, radicand, Node::num(0.5)),
+    };
+    simplify(&Node::binary(Mul, Node::num(k as f32), root))
+}
+
+fn quadratic(a: &Rc<Node>, b: &Rc<Node>, c: &Rc<Node>) -> Vec<Rc<Node>> {
+    let disc = cancel(&Node::binary(
+        Sub,
+        Node::binary(Pow, b.clone(), Node::num(2.0)),
+        Node::binary(Mul, Node::binary(Mul, Node::num(4.0), a.clone()), c.clone())
+    ));
+    let twice_a = Node::binary(Mul, Node::num(2.0), a.clone());
+    let minus_b = Node::unary(Neg, b.clone());
+    match value(&disc) {
+        Some(disc) if disc < 0.0 => Vec::new(),
+        Some(0.0) => vec![cancel(&Node::binary(Div, minus_b, twice_a))],
+        _ => {
+            let root = sqrt(&disc);
+            vec![
+                cancel(&Node::binary(Div, Node::binary(Sub, minus_b.clone(), root.clone()), twice_a.clone())),
+                cancel(&Node::binary(Div, Node::binary(Add, minus_b, root), twice_a)),
+            ]
+        },
+    }
+}
+
+// The real cube root, written so that negative radicands stay defined.
+fn cbrt(node: Rc<Node>, val: f64) -> Rc<Node> {
+    let node = simplify(&node);
+    let third = Node::binary(Div, Node::num(1.0), Node::num(3.0));
+    if is_zero(&node) {
+        node
+    } else if val < 0.0 {
+        Node::unary(Neg, Node::binary(Pow, Node::unary(Neg, node), third))
+    } else {
+        Node::binary(Pow, node, third)
+    }
+}
+
+// The real roots of a cubic with constant coefficients, through the depressed
+// cubic `t^3 + p t + q` with `x = t - b / (3 a)`. Cardano's formula gives a
+// single real root; three real roots would need complex cube roots there, so
+// they come from the trigonometric form `t = 2 m cos(θ)` with `m = (-p / 3)^0.5`
+// and `cos(3 θ) = 3 q / (2 p m)` instead.
+fn cubic(a: &Rc<Node>, b: &Rc<Node>, c: &Rc<Node>, d: &Rc<Node>) -> Option<Vec<Rc<Node>>> {
+    let (av, bv, cv, dv) = (value(a)?, value(b)?, value(c)?, value(d)?);
+    let pv = (3.0 * av * cv - bv * bv) / (3.0 * av * av);
+    let qv = (2.0 * bv * bv * bv - 9.0 * av * bv * cv + 27.0 * av * av * dv) / (27.0 * av * av * av);
+    let disc_v = qv * qv / 4.0 + pv * pv * pv / 27.0;
+
+    let num = |val: f32| Node::num(val);
+    let mul = |lhs: Rc<Node>, rhs: Rc<Node>| Node::binary(Mul, lhs, rhs);
+    let pow = |base: &Rc<Node>, n: f32| Node::binary(Pow, base.clone(), num(n));
+    let p = Node::binary(
+        Div,
+        Node::binary(Sub, mul(mul(num(3.0), a.clone()), c.clone()), pow(b, 2.0)),
+        mul(num(3.0), pow(a, 2.0))
+    );
+    let q = Node::binary(
+        Div,
+        Node::binary(
+            Add,
+            Node::binary(Sub, mul(num(2.0), pow(b, 3.0)), mul(mul(mul(num(9.0), a.clone()), b.clone()), c.clone())),
+            mul(mul(num(27.0), pow(a, 2.0)), d.clone())
+        ),
+        mul(num(27.0), pow(a, 3.0))
+    );
+    let (p, q) = (cancel(&p), cancel(&q));
+    let disc = Node::binary(
+        Add,
+        Node::binary(Div, pow(&q, 2.0), num(4.0)),
+        Node::binary(Div, pow(&p, 3.0), num(27.0))
+    );
+    let shift = Node::binary(Div, b.clone(), mul(num(3.0), a.clone()));
+    let shifted = |t: Rc<Node>| Node::binary(Sub, t, shift.clone());
+
+    if pv == 0.0 && qv == 0.0 {
+        return Some(vec![Node::unary(Neg, shift)]);
+    }
+    // A double root `-3 q / (2 p)` and a simple one `3 q / p`.
+    if disc_v == 0.0 {
+        let ratio = Node::binary(Div, mul(num(3.0), q), p);
+        return Some(vec![shifted(Node::unary(Neg, Node::binary(Div, ratio.clone(), num(2.0)))), shifted(ratio)]);
+    }
+    if disc_v < 0.0 {
+        let m = Node::binary(Pow, simplify(&Node::binary(Div, Node::unary(Neg, p.clone()), num(3.0))), num(0.5));
+        let cos_3t = simplify(&Node::binary(Div, mul(num(3.0), q), mul(mul(num(2.0), p), m.clone())));
+        // `acos(c) = pi / 2 - asin(c)`.
+        let pi = Node::constant(CKind::Pi);
+        let acos = Node::binary(Sub, Node::binary(Div, pi.clone(), num(2.0)), Node::unary(Asin, cos_3t));
+        let theta = Node::binary(Div, acos, num(3.0));
+        return Some((0..3).map(|k| {
+            let turn = Node::binary(Div, mul(num(2.0 * k as f32), pi.clone()), num(3.0));
+            let angle = if k == 0 { theta.clone() } else { Node::binary(Sub, theta.clone(), turn) };
+            shifted(mul(mul(num(2.0), m.clone()), Node::unary(Cos, angle)))
+        }).collect());
+    }
+
+    let half_q = simplify(&Node::binary(Div, q, num(2.0)));
+    let root = Node::binary(Pow, simplify(&disc), num(0.5));
+    let u = Node::binary(Sub, root.clone(), half_q.clone());
+    let v = Node::unary(Neg, Node::binary(Add, half_q, root));
+    let disc_root = disc_v.sqrt();
+    Some(vec![shifted(Node::binary(Add, cbrt(u, disc_root - qv / 2.0), cbrt(v, -qv / 2.0 - disc_root)))])
+}
+
+// Roots of the polynomial with coefficients `coeffs`, lowest power first, and the
+// coefficients of any factor they leave unsolved.
+fn polynomial(coeffs: &[Rc<Node>]) -> (Vec<Rc<Node>>, Option<Vec<Rc<Node>>>) {
+    let mut roots = Vec::new();
+    let mut coeffs = coeffs.to_vec();
+    if is_zero(&coeffs[0]) {
+        roots.push(Node::num(0.0));
+        while is_zero(&coeffs[0]) {
+            coeffs.remove(0);
+        }
+    }
+    if coeffs.len() > 2 {
+        if let Some(ints) = integers(&coeffs) {
+            let (rational, rest) = rational_roots(ints);
+            for (p, q) in rational {
+                roots.push(Node::binary(Div, Node::num(p as f32), Node::num(q as f32)));
+            }
+            coeffs = rest.iter().map(|coeff| Node::num(*coeff as f32)).collect();
+        }
+    }
+    match coeffs.len() {
+        1 => {},
+        2 => roots.push(Node::binary(Div, Node::unary(Neg, coeffs[0].clone()), coeffs[1].clone())),
+        3 => roots.extend(quadratic(&coeffs[2], &coeffs[1], &coeffs[0])),
+        4 => match cubic(&coeffs[3], &coeffs[2], &coeffs[1], &coeffs[0]) {
+            Some(cubic_roots) => roots.extend(cubic_roots),
+            None => return (roots, Some(coeffs)),
+        },
+        _ => return (roots, Some(coeffs)),
+    }
+    (roots, None)
+}
+
+// Undoes the outermost operation of `lhs` until `var` stands alone, giving every
+// solution of `lhs == rhs` where `var` appears once. Periodic functions have
+// infinitely many solutions, so they are not inverted. Raising both sides to a
+// power can add solutions, which `solve` checks for.
+fn isolate(lhs: &Rc<Node>, rhs: &Rc<Node>, var: &str) -> Option<Vec<Rc<Node>>> {
+    if depends(rhs, var) {
+        return if depends(lhs, var) { None } else { isolate(rhs, lhs, var) };
+    }
+    let r = rhs.clone();
+    match &**lhs {
+        Var { .. } => Some(vec![r]),
+        BinaryOperator { kind, lhs: a, rhs: b } => {
+            let (in_a, in_b) = (depends(a, var), depends(b, var));
+            if in_a && in_b {
+                return None;
+            }
+            match (kind, in_a) {
+                (Add, true) => isolate(a, &Node::binary(Sub, r, b.clone()), var),
+                (Add, false) => isolate(b, &Node::binary(Sub, r, a.clone()), var),
+                (Sub, true) => isolate(a, &Node::binary(Add, r, b.clone()), var),
+                (Sub, false) => isolate(b, &Node::binary(Sub, a.clone(), r), var),
+                (Mul, true) => isolate(a, &Node::binary(Div, r, b.clone()), var),
+                (Mul, false) => isolate(b, &Node::binary(Div, r, a.clone()), var),
+                (Div, true) => isolate(a, &Node::binary(Mul, r, b.clone()), var),
+                // `a / b` is never zero or infinite.
+                (Div, false) if value(&r).is_some_and(|rv| rv == 0.0 || !rv.is_finite()) => Some(Vec::new()),
+                (Div, false) => isolate(b, &Node::binary(Div, a.clone(), r), var),
+                (Pow, true) => {
+                    let inverse = Node::binary(Div, Node::num(1.0), b.clone());
+                    let rv = value(&r);
+                    match **b {
+                        Num { val } if val.fract() == 0.0 && val % 2.0 == 0.0 => {
+                            if rv.is_some_and(|rv| rv < 0.0) {
+                                return Some(Vec::new());
+                            }
+                            let root = Node::binary(Pow, r, inverse);
+                            let mut roots = isolate(a, &Node::unary(Neg, root.clone()), var)?;
+                            roots.extend(isolate(a, &root, var)?);
+                            Some(roots)
+                        },
+                        Num { val } if val.fract() == 0.0 && rv.is_some_and(|rv| rv < 0.0) => {
+                            isolate(a, &Node::unary(Neg, Node::binary(Pow, Node::unary(Neg, r), inverse)), var)
+                        },
+                        _ => isolate(a, &Node::binary(Pow, r, inverse), var),
+                    }
+                },
+                (Pow, false) => isolate(b, &Node::binary(Div, Node::unary(Log, r), Node::unary(Log, a.clone())), var),
+            }
+        },
+        UnaryOperator { kind, operand } => {
+            let rv = value(&r);
+            match kind {
+                Neg => isolate(operand, &Node::unary(Neg, r), var),
+                Exp if rv.is_some_and(|rv| rv <= 0.0) => Some(Vec::new()),
+                Exp => isolate(operand, &Node::unary(Log, r), var),
+                Log => isolate(operand, &Node::unary(Exp, r), var),
+                Sin | Cos if rv.is_some_and(|rv| rv.abs() > 1.0) => Some(Vec::new()),
+                Asin => isolate(operand, &Node::unary(Sin, r), var),
+                // Variables are real, so these leave real operands alone.
+                Re | Conj => isolate(operand, &r, var),
+                Abs if rv.is_some_and(|rv| rv < 0.0) => Some(Vec::new()),
+                Abs => {
+                    let mut roots = isolate(operand, &Node::unary(Neg, r.clone()), var)?;
+                    roots.extend(isolate(operand, &r, var)?);
+                    Some(roots)
+                },
+                Sin | Cos | Tan | Im | Arg => None,
+            }
+        },
+        Num { .. } | Const { .. } | List { .. } | Derivative { .. } | Equation { .. } | Unsolved { .. } => None,
+    }
+}
+
+// Whether `root` solves `lhs == rhs`, as far as can be told: only constant
+// roots are checked.
+fn satisfies(lhs: &Rc<Node>, rhs: &Rc<Node>, var: &Rc<Node>, root: &Rc<Node>) -> bool {
+    let at_root = |side: &Rc<Node>| value(&Node::substitute(side, var, root));
+    match (at_root(lhs), at_root(rhs)) {
+        (Some(l), Some(r)) => (l - r).abs() <= 1e-9 * l.abs().max(r.abs()).max(1.0),
+        _ => true,
+    }
+}
+
+fn unknown(var: &Rc<Node>) -> Result<String, String> {
+    match &**var {
+        Var { name, point: None, .. } => Ok(name.clone()),
+        Var { name, .. } => Err(format!("{} is defined and cannot be solved for", name)),
+        _ => Err("expected a variable to solve for".to_string()),
+    }
+}
+
+// The simplified sides of `equation`; an expression stands for `expression == 0`.
+fn sides(equation: &Rc<Node>) -> Result<(Rc<Node>, Rc<Node>), String> {
+    match &**equation {
+        Equation { lhs, rhs } => Ok((simplify(lhs), simplify(rhs))),
+        List { .. } | Unsolved { .. } => Err("expected an equation".to_string()),
+        _ => Ok((simplify(equation), Node::num(0.0))),
+    }
+}
+
+// Splits `f` into its coefficients in `vars` and the constant it leaves on the
+// other side, failing unless `f` is linear in `vars`.
+fn linear(f: &Rc<Node>, vars: &[Rc<Node>], names: &[String]) -> Result<(Vec<Rc<Node>>, Rc<Node>), String> {
+    let nonlinear = || format!("{} is not linear in {}", f, names.join(", "));
+    let mut coeffs = Vec::new();
+    for name in names {
+        let poly = poly::coefficients(f, name).ok_or_else(nonlinear)?;
+        if poly.len() > 2 {
+            return Err(nonlinear());
+        }
+        let coeff = poly.get(1).cloned().unwrap_or_else(|| Node::num(0.0));
+        if names.iter().any(|name| depends(&coeff, name)) {
+            return Err(nonlinear());
+        }
+        coeffs.push(coeff);
+    }
+    let constant = vars.iter().fold(f.clone(), |f, var| Node::substitute(&f, var, &Node::num(0.0)));
+    Ok((coeffs, simplify(&Node::unary(Neg, constant))))
+}
+
+// Solves linear equations in `vars` by fraction-free Gauss-Jordan elimination.
+// Every row update is divided by the previous pivot, which is exact. Entries
+// are kept cancelled, so an entry that is zero as a rational function is
+// literally `0` and pivots and residuals can be tested as they stand. Each
+// pivot row then reads `pivot * var == rhs`.
+fn system(equations: &[Rc<Node>], vars: &[Rc<Node>]) -> Result<Rc<Node>, String> {
+    let names = vars.iter().map(unknown).collect::<Result<Vec<String>, String>>()?;
+    let mut rows = Vec::new();
+    for equation in equations {
+        let (lhs, rhs) = sides(equation)?;
+        let (mut row, constant) = linear(&simplify(&Node::binary(Sub, lhs, rhs)), vars, &names)?;
+        row.push(constant);
+        rows.push(row.iter().map(cancel).collect::<Vec<Rc<Node>>>());
+    }
+
+    let m = vars.len();
+    let mut prev = Node::num(1.0);
+    let mut pivots = Vec::new();
+    for col in 0..m {
+        let k = pivots.len();
+        let Some(r) = (k..rows.len()).find(|r| !is_zero(&rows[*r][col])) else { continue };
+        rows.swap(k, r);
+        let pivot = rows[k][col].clone();
+        for i in (0..rows.len()).filter(|i| *i != k) {
+            let factor = rows[i][col].clone();
+            rows[i][col] = Node::num(0.0);
+            for j in (0..=m).filter(|j| *j != col) {
+                let entry = Node::binary(
+                    Sub,
+                    Node::binary(Mul, pivot.clone(), rows[i][j].clone()),
+                    Node::binary(Mul, factor.clone(), rows[k][j].clone())
+                );
+                rows[i][j] = cancel(&Node::binary(Div, entry, prev.clone()));
+            }
+        }
+        prev = pivot;
+        pivots.push(col);
+    }
+
+    if let Some(row) = rows[pivots.len()..].iter().find(|row| !is_zero(&row[m])) {
+        return Err(format!("the system is inconsistent: it requires 0 == {}", row[m]));
+    }
+    if pivots.len() < m {
+        let free = (0..m).filter(|col| !pivots.contains(col)).map(|col| names[col].clone()).collect::<Vec<String>>();
+        return Err(format!("the system is underdetermined: {} can take any value", free.join(", ")));
+    }
+    let solutions = pivots.iter().enumerate().map(|(k, col)| {
+        let val = cancel(&Node::binary(Div, rows[k][m].clone(), rows[k][*col].clone()));
+        Node::equation(vars[*col].clone(), val)
+    }).collect::<Vec<Rc<Node>>>();
+    Ok(Node::list(solutions))
+}
+
+// Solves `equation` for `var`, giving the list of solutions as equations
+// `var == root`. A factor or equation without a closed form is kept as an
+// unevaluated `solve`. Lists of equations and variables are solved as a linear
+// system.
+pub fn solve(equation: &Rc<Node>, var: &Rc<Node>) -> Result<Rc<Node>, String> {
+    if let (List { items: equations }, List { items: vars }) = (&**equation, &**var) {
+        return system(equations, vars);
+    }
+    let name = unknown(var)?;
+    let (lhs, rhs) = sides(equation)?;
+    let unsolved = |lhs: Rc<Node>, rhs: Rc<Node>| Node::unsolved(Node::equation(lhs, rhs), var.clone());
+
+    let f = simplify(&Node::binary(Sub, lhs.clone(), rhs.clone()));
+    if !depends(&f, &name) {
+        return match *f {
+            Num { val: 0.0 } => Err(format!("every value of {} solves {}", name, equation)),
+            Num { .. } => Ok(Node::list(Vec::new())),
+            _ => Ok(unsolved(lhs, rhs)),
+        };
+    }
+    let (roots, rest) = match poly::coefficients(&f, &name) {
+        Some(coeffs) => polynomial(&coeffs),
+        None => match isolate(&lhs, &rhs, &name) {
+            Some(roots) => (roots.into_iter().filter(|root| satisfies(&lhs, &rhs, var, root)).collect(), None),
+            None => return Ok(unsolved(lhs, rhs)),
+        },
+    };
+
+    let mut roots = roots.iter().map(simplify).collect::<Vec<Rc<Node>>>();
+    let vals = roots.iter().map(value).collect::<Option<Vec<f64>>>();
+    if let Some(vals) = vals {
+        let mut order = (0..roots.len()).collect::<Vec<usize>>();
+        order.sort_by(|i, j| vals[*i].total_cmp(&vals[*j]));
+        roots = order.iter().map(|i| roots[*i].clone()).collect();
+    }
+    roots.dedup_by(|a, b| Node::equiv(a, b));
+
+    let mut solutions = roots.into_iter().map(|root| Node::equation(var.clone(), root)).collect::<Vec<Rc<Node>>>();
+    if let Some(rest) = rest {
+        let factor = rest.iter().enumerate().rev().fold(Node::num(0.0), |sum, (i, coeff)| {
+            let term = Node::binary(Mul, coeff.clone(), Node::binary(Pow, var.clone(), Node::num(i as f32)));
+            Node::binary(Add, sum, term)
+        });
+        solutions.push(unsolved(simplify(&factor), Node::num(0.0)));
+    }
+    Ok(Node::list(solutions))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    fn solved(src: &str) -> String {
+        let equation = parse(&format!("var x, y, a; {}", src));
+        solve(&equation, &Node::var("x", None)).unwrap().to_string()
+    }
+
+    #[test]
+    fn polynomials() {
+        assert_eq!(solved("x ^ 3 - 6 * x ^ 2 + 11 * x - 6 == 0"), "[x == 1, x == 2, x == 3]");
+        assert_eq!(solved("x ^ 2 - 2 == 0"), "[x == -(2 ^ 0.5), x == 2 ^ 0.5]");
+        assert_eq!(solved("x ^ 2 == a"), "[x == -(a ^ 0.5), x == a ^ 0.5]");
+        assert_eq!(solved("x ^ 3 - 2 == 0"), "[x == 2 ^ (1 / 3)]");
+        assert_eq!(solved("x ^ 2 + 1 == 0"), "[]");
+    }
+
+    #[test]
+    fn three_real_roots_of_a_cubic() {
+        let roots = parse(&format!("var x; {}", solved("x ^ 3 - 3 * x + 1 == 0").replace("x == ", "")));
+        let roots = crate::matrix::items(&roots).unwrap().iter().map(|root| value(root).unwrap()).collect::<Vec<f64>>();
+        assert_eq!(roots.len(), 3);
+        for root in roots {
+            assert!((root.powi(3) - 3.0 * root + 1.0).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn isolating() {
+        assert_eq!(solved("exp(x) == 3"), "[x == log(3)]");
+        assert_eq!(solved("abs(x - 1) == 2"), "[x == -1, x == 3]");
+        assert_eq!(solved("x ^ (1 / 2) == 3"), "[x == 9]");
+        // Squaring both sides would give `x == 1`, which is no solution.
+        assert_eq!(solved("x ^ (1 / 2) == -1"), "[]");
+        assert_eq!(solved("1 / x == 0"), "[]");
+        assert_eq!(solved("sin(x) == 1 / 2"), "solve(sin(x) == 0.5, x)");
+    }
+
+    #[test]
+    fn linear_systems() {
+        let equations = parse("var x, y, a; [x + y == 3, x - y == 1]");
+        let vars = parse("var x, y; [x, y]");
+        assert_eq!(solve(&equations, &vars).unwrap().to_string(), "[x == 2, y == 1]");
+        let singular = parse("var x, y, a; [a * x + y == 1, 2 * a * x + 2 * y == 3]");
+        assert!(solve(&singular, &vars).unwrap_err().contains("inconsistent"));
+    }
+}
@@ -0,0 +1,52 @@
+// Points `from, from + step, ...` up to and including `to`, computed by
+// multiplication so that rounding errors do not accumulate along the range. The
+// count allows for steps such as 0.1 that constants only hold to f32 precision.
+pub fn grid(from: f64, to: f64, step: f64) -> Result<Vec<f64>, String> {
+    if step <= 0.0 || !step.is_finite() {
+        return Err(format!("step must be positive, found {}", step));
+    }
+    if from > to || from.is_nan() || to.is_nan() {
+        return Err(format!("empty range {}..{}", from, to));
+    }
+    let steps = (to - from) / step;
+    let count = (steps * (1.0 + 1e-6) + 1e-9).floor() as usize + 1;
+    Ok((0..count).map(|i| from + i as f64 * step).collect())
+}
+
+fn csv_field(text: &str) -> String {
+    if text.contains(',') || text.contains('"') {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+// Renders one column per header, either as comma-separated values with full
+// precision or as right-aligned text with the decimal points lined up.
+pub fn render(headers: &[String], columns: &[Vec<f64>], csv: bool) -> String {
+    let rows = columns.iter().map(|column| column.len()).max().unwrap_or(0);
+    let mut cells = vec![headers.to_vec()];
+    for i in 0..rows {
+        cells.push(columns.iter().map(|column| {
+            if csv { format!("{}", column[i]) } else { format!("{:.6}", column[i]) }
+        }).collect());
+    }
+
+    let mut text = String::new();
+    if csv {
+        for row in &cells {
+            text.push_str(&row.iter().map(|cell| csv_field(cell)).collect::<Vec<String>>().join(","));
+            text.push('\n');
+        }
+        return text;
+    }
+    let widths = (0..headers.len())
+        .map(|j| cells.iter().map(|row| row[j].chars().count()).max().unwrap_or(0))
+        .collect::<Vec<usize>>();
+    for row in &cells {
+        let line = row.iter().zip(&widths).map(|(cell, width)| format!("{:>1$}", cell, width)).collect::<Vec<String>>();
+        text.push_str(line.join("  ").trim_end());
+        text.push('\n');
+    }
+    text
+}
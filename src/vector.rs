@@ -0,0 +1,200 @@
+use std::collections::HashSet;
+use std::rc::Rc;
+
+use crate::factor::cancel;
+use crate::matrix::items;
+use crate::node::*;
+use crate::simplify::simplify;
+
+use BKind::*;
+use UKind::*;
+use Node::*;
+
+// The results are rational in the variables far more often than not, so
+// common factors such as the scale factors are cancelled.
+fn tidy(node: &Rc<Node>) -> Rc<Node> {
+    cancel(&simplify(node))
+}
+
+// Orthogonal coordinate systems, described by their scale factors: the length
+// of a unit step in each coordinate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Coords {
+    Cartesian,
+    // `(r, theta, z)`.
+    Cylindrical,
+    // `(r, theta, phi)` with `theta` measured from the z axis.
+    Spherical,
+}
+
+impl Coords {
+    pub fn from_name(name: &str) -> Option<Coords> {
+        match name {
+            "cartesian" => Some(Coords::Cartesian),
+            "cylindrical" => Some(Coords::Cylindrical),
+            "spherical" => Some(Coords::Spherical),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Coords::Cartesian => "cartesian",
+            Coords::Cylindrical => "cylindrical",
+            Coords::Spherical => "spherical",
+        }
+    }
+
+    fn scale_factors(&self, vars: &[Rc<Node>]) -> Result<Vec<Rc<Node>>, String> {
+        if *self != Coords::Cartesian && vars.len() != 3 {
+            return Err(format!("{} coordinates have 3 variables, found {}", self.name(), vars.len()));
+        }
+        Ok(match self {
+            Coords::Cartesian => vars.iter().map(|_| Node::num(1.0)).collect(),
+            Coords::Cylindrical => vec![Node::num(1.0), vars[0].clone(), Node::num(1.0)],
+            Coords::Spherical => vec![
+                Node::num(1.0),
+                vars[0].clone(),
+                Node::binary(Mul, vars[0].clone(), Node::unary(Sin, vars[1].clone())),
+            ],
+        })
+    }
+}
+
+fn variables(vars: &Rc<Node>) -> Result<Vec<Rc<Node>>, String> {
+    let vars = items(vars).ok_or_else(|| format!("expected a list of variables, found {}", vars))?;
+    match vars.iter().find(|var| !matches!(***var, Var { point: None, .. })) {
+        Some(var) => Err(format!("{} is not a free variable", var)),
+        None if vars.is_empty() => Err("expected at least one variable".to_string()),
+        None => Ok(vars),
+    }
+}
+
+// Lists anywhere in `f`, as in `2 * [x, y]`, make it a vector rather than a
+// scalar field.
+fn scalar(f: &Rc<Node>) -> Result<(), String> {
+    fn visit(node: &Rc<Node>, seen: &mut HashSet<*const Node>) -> bool {
+        if !seen.insert(Rc::as_ptr(node)) {
+            return true;
+        }
+        match &**node {
+            List { .. } | Equation { .. } | Unsolved { .. } => false,
+            BinaryOperator { lhs, rhs, .. } => visit(lhs, seen) && visit(rhs, seen),
+            UnaryOperator { operand, .. } => visit(operand, seen),
+            Var { point: Some(point), .. } => visit(point, seen),
+            Var { point: None, .. } | Derivative { .. } | Num { .. } | Const { .. } => true,
+        }
+    }
+    if visit(f, &mut HashSet::new()) { Ok(()) } else { Err(format!("expected a scalar field, found {}", f)) }
+}
+
+// The components of a vector field with one per variable.
+fn components(field: &Rc<Node>, vars: &[Rc<Node>]) -> Result<Vec<Rc<Node>>, String> {
+    match items(field) {
+        Some(items) if items.len() == vars.len() => {
+            items.iter().try_for_each(scalar)?;
+            Ok(items)
+        },
+        Some(items) => Err(format!("expected a field of {} components, found {}", vars.len(), items.len())),
+        None => Err(format!("expected a vector field, found {}", field)),
+    }
+}
+
+fn product(factors: &[Rc<Node>]) -> Rc<Node> {
+    factors[1..].iter().fold(factors[0].clone(), |acc, factor| Node::binary(Mul, acc, factor.clone()))
+}
+
+fn sum(terms: Vec<Rc<Node>>) -> Rc<Node> {
+    terms[1..].iter().fold(terms[0].clone(), |acc, term| Node::binary(Add, acc, term.clone()))
+}
+
+// `f_i / h_i`.
+fn per_scale(nodes: Vec<Rc<Node>>, h: &[Rc<Node>]) -> Vec<Rc<Node>> {
+    nodes.into_iter().zip(h).map(|(node, h)| Node::binary(Div, node, h.clone())).collect()
+}
+
+fn gradient(f: &Rc<Node>, vars: &[Rc<Node>], h: &[Rc<Node>]) -> Vec<Rc<Node>> {
+    per_scale(vars.iter().map(|var| Node::dif(f, var)).collect(), h)
+}
+
+// `1 / (h_1 ... h_n) * sum_i d(F_i h_1 ... h_n / h_i) / dq_i`.
+fn div_of(field: &[Rc<Node>], vars: &[Rc<Node>], h: &[Rc<Node>]) -> Rc<Node> {
+    let volume = product(h);
+    let terms = field.iter().zip(vars).zip(h).map(|((f, var), h)| {
+        Node::dif(&Node::binary(Div, Node::binary(Mul, f.clone(), volume.clone()), h.clone()), var)
+    }).collect();
+    Node::binary(Div, sum(terms), volume)
+}
+
+pub fn grad(f: &Rc<Node>, vars: &Rc<Node>, coords: Coords) -> Result<Rc<Node>, String> {
+    scalar(f)?;
+    let vars = variables(vars)?;
+    let h = coords.scale_factors(&vars)?;
+    Ok(Node::list(gradient(f, &vars, &h).iter().map(tidy).collect()))
+}
+
+pub fn divergence(field: &Rc<Node>, vars: &Rc<Node>, coords: Coords) -> Result<Rc<Node>, String> {
+    let vars = variables(vars)?;
+    let h = coords.scale_factors(&vars)?;
+    Ok(tidy(&div_of(&components(field, &vars)?, &vars, &h)))
+}
+
+// In three dimensions a vector; in the plane the scalar `dF_2/dx - dF_1/dy`.
+pub fn curl(field: &Rc<Node>, vars: &Rc<Node>, coords: Coords) -> Result<Rc<Node>, String> {
+    let vars = variables(vars)?;
+    let h = coords.scale_factors(&vars)?;
+    let f = components(field, &vars)?;
+    // `(d(h_k F_k)/dq_j - d(h_j F_j)/dq_k) / (h_j h_k)` for each cyclic `(i, j, k)`.
+    let rotation = |j: usize, k: usize| {
+        let scaled = |i: usize| Node::binary(Mul, h[i].clone(), f[i].clone());
+        let diff = Node::binary(Sub, Node::dif(&scaled(k), &vars[j]), Node::dif(&scaled(j), &vars[k]));
+        tidy(&Node::binary(Div, diff, Node::binary(Mul, h[j].clone(), h[k].clone())))
+    };
+    match vars.len() {
+        2 => Ok(rotation(0, 1)),
+        3 => Ok(Node::list(vec![rotation(1, 2), rotation(2, 0), rotation(0, 1)])),
+        n => Err(format!("curl is defined in 2 or 3 dimensions, found {}", n)),
+    }
+}
+
+pub fn laplacian(f: &Rc<Node>, vars: &Rc<Node>, coords: Coords) -> Result<Rc<Node>, String> {
+    scalar(f)?;
+    let vars = variables(vars)?;
+    let h = coords.scale_factors(&vars)?;
+    Ok(tidy(&div_of(&gradient(f, &vars, &h), &vars, &h)))
+}
+
+// The rate of change of `f` along `direction`, which is normalised first.
+pub fn directional(f: &Rc<Node>, vars: &Rc<Node>, direction: &Rc<Node>) -> Result<Rc<Node>, String> {
+    scalar(f)?;
+    let vars = variables(vars)?;
+    let u = components(direction, &vars)?;
+    let slope = sum(vars.iter().zip(&u).map(|(var, u)| Node::binary(Mul, Node::dif(f, var), u.clone())).collect());
+    let length = Node::binary(Pow, sum(u.iter().map(|u| Node::binary(Pow, u.clone(), Node::num(2.0))).collect()), Node::num(0.5));
+    Ok(tidy(&Node::binary(Div, slope, length)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    #[test]
+    fn vector_fields_are_not_scalar_fields() {
+        let vars = parse("var x, y; [x, y]");
+        assert!(laplacian(&parse("var x, y; [x, y]"), &vars, Coords::Cartesian).unwrap_err().contains("scalar field"));
+        assert!(grad(&parse("var x, y; 2 * [x, y]"), &vars, Coords::Cartesian).unwrap_err().contains("scalar field"));
+        assert!(directional(&parse("var x; [x]"), &vars, &parse("[1, 1]")).unwrap_err().contains("scalar field"));
+        assert!(divergence(&parse("var x, y; [[x], y]"), &vars, Coords::Cartesian).unwrap_err().contains("scalar field"));
+        assert_eq!(grad(&parse("var x, y; x * y"), &vars, Coords::Cartesian).unwrap().to_string(), "[y, x]");
+    }
+
+    #[test]
+    fn spherical_laplacian() {
+        let vars = parse("var r, theta, phi; [r, theta, phi]");
+        let laplace = |src: &str| laplacian(&parse(&format!("var r, theta, phi; {}", src)), &vars, Coords::Spherical).unwrap().to_string();
+        // The potential of a point charge is harmonic.
+        assert_eq!(laplace("1 / r"), "0");
+        assert_eq!(laplace("r ^ 2 * cos(theta)"), "4 * cos(theta)");
+    }
+}